@@ -1,23 +1,430 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Ok, Result};
+use clap::ArgMatches;
+use clap::parser::ValueSource;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct Config {
+    pub input_encoding: Option<String>,
     pub wpm: Option<u32>,
+    pub spw: Option<f64>,
+    pub theme: Option<crate::ffmpeg::Theme>,
     pub text_color: Option<String>,
     pub bg_color: Option<String>,
+    pub bg_gradient: Option<String>,
+    pub vignette: Option<bool>,
+    pub blur_background: Option<f64>,
+    pub background_image: Option<PathBuf>,
     pub focus_color: Option<String>,
     pub secondary_color: Option<String>,
+    pub countdown: Option<u32>,
+    pub outro_text: Option<String>,
+    pub outro_duration: Option<f64>,
+    pub max_duration: Option<f64>,
+    pub chunk_size: Option<usize>,
     pub rest_duration: Option<f64>,
+    pub word_gap: Option<f64>,
+    pub comma_pause: Option<f64>,
+    pub sentence_pause: Option<f64>,
+    pub paragraph_pause: Option<f64>,
+    pub periodic_rest_interval: Option<f64>,
+    pub abbreviations: Option<Vec<String>>,
+    pub jieba_dict: Option<PathBuf>,
+    pub strip_markdown: Option<bool>,
+    pub no_cjk: Option<bool>,
+    pub max_word_length: Option<usize>,
     pub focus_lines: Option<bool>,
-    pub bgm_location: Option<String>,
+    pub focus_thickness: Option<u32>,
+    pub focus_offset: Option<f64>,
+    pub orp: Option<bool>,
+    pub pivot_color: Option<String>,
+    pub auto_emphasize: Option<bool>,
+    pub emphasis_color: Option<String>,
+    pub emphasis_min_length: Option<usize>,
+    pub no_emphasis_stoplist: Option<bool>,
+    pub context_words: Option<bool>,
+    pub context_color: Option<String>,
+    pub show_progress_overlay: Option<bool>,
+    pub watermark: Option<crate::ffmpeg::Watermark>,
+    pub watermark_text: Option<String>,
+    pub watermark_position: Option<crate::ffmpeg::WatermarkPosition>,
+    // A single path (legacy) or a playlist
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub bgm_location: Option<Vec<String>>,
+    pub bgm_shuffle: Option<bool>,
+    pub bgm_loop: Option<i32>,
+    pub bgm_volume: Option<f64>,
+    pub bgm_fade_in: Option<f64>,
+    pub bgm_fade_out: Option<f64>,
+    pub tts: Option<String>,
+    pub tts_command: Option<String>,
     pub font_location: Option<String>,
-    pub overwrite_output_file: Option<bool>,
+    #[serde(alias = "overwrite_output_file")]
+    pub overwrite: Option<bool>,
+    pub resolution: Option<String>,
+    pub fps: Option<u32>,
+    pub fontsize: Option<u32>,
+    pub fontsize_small: Option<u32>,
+    pub long_word_threshold: Option<usize>,
+    pub font_size_auto: Option<bool>,
+    pub margin: Option<u32>,
+    pub letter_spacing: Option<u32>,
+    pub line_spacing: Option<i32>,
+    pub vertical: Option<bool>,
+    pub align: Option<crate::ffmpeg::Align>,
+    pub word_x: Option<String>,
+    pub renderer: Option<crate::ffmpeg::Renderer>,
+    pub ass_word_threshold: Option<usize>,
+    pub vcodec: Option<String>,
+    pub preset: Option<String>,
+    pub crf: Option<u32>,
+    pub pix_fmt: Option<String>,
+    pub acodec: Option<String>,
+    pub abitrate: Option<String>,
+    pub jobs: Option<usize>,
+    pub hwenc: Option<crate::ffmpeg::HwEncode>,
+    pub hwaccel: Option<String>,
 }
 
-fn get_config_path() -> Result<PathBuf> {
+fn one_or_many<'de, D>(deserializer: D) -> std::result::Result<Option<Vec<String>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Option::<OneOrMany>::deserialize(deserializer).map(|value| {
+        value.map(|value| match value {
+            OneOrMany::One(path) => vec![path],
+            OneOrMany::Many(paths) => paths,
+        })
+    })
+}
+
+impl Config {
+    // Snapshot the effective settings from parsed (and merged) args
+    fn from_args(args: &crate::Args) -> Self {
+        Config {
+            input_encoding: args.input_encoding.clone(),
+            wpm: Some(args.wpm),
+            spw: args.spw,
+            theme: args.theme,
+            text_color: Some(args.text_color.clone()),
+            bg_color: Some(args.bg_color.clone()),
+            bg_gradient: args.bg_gradient.clone(),
+            vignette: Some(args.vignette),
+            blur_background: args.blur_background,
+            background_image: args.background_image.clone(),
+            focus_color: args.focus_color.clone(),
+            secondary_color: Some(args.secondary_color.clone()),
+            countdown: Some(args.countdown),
+            outro_text: args.outro_text.clone(),
+            outro_duration: Some(args.outro_duration),
+            max_duration: args.max_duration,
+            chunk_size: Some(args.chunk_size),
+            rest_duration: Some(args.rest_duration),
+            word_gap: Some(args.word_gap),
+            comma_pause: Some(args.comma_pause),
+            sentence_pause: Some(args.sentence_pause),
+            paragraph_pause: Some(args.paragraph_pause),
+            periodic_rest_interval: Some(args.periodic_rest_interval),
+            abbreviations: Some(args.abbreviations.clone()),
+            jieba_dict: args.jieba_dict.clone(),
+            strip_markdown: Some(args.strip_markdown),
+            no_cjk: Some(args.no_cjk),
+            max_word_length: Some(args.max_word_length),
+            focus_lines: Some(args.focus_lines),
+            focus_thickness: Some(args.focus_thickness),
+            focus_offset: Some(args.focus_offset),
+            orp: Some(args.orp),
+            pivot_color: args.pivot_color.clone(),
+            auto_emphasize: Some(args.auto_emphasize),
+            emphasis_color: Some(args.emphasis_color.clone()),
+            emphasis_min_length: Some(args.emphasis_min_length),
+            no_emphasis_stoplist: Some(args.no_emphasis_stoplist),
+            context_words: Some(args.context_words),
+            context_color: args.context_color.clone(),
+            show_progress_overlay: Some(args.show_progress_overlay),
+            watermark: Some(args.watermark),
+            watermark_text: args.watermark_text.clone(),
+            watermark_position: Some(args.watermark_position),
+            bgm_location: Some(args.bgm_location.clone()),
+            bgm_shuffle: Some(args.bgm_shuffle),
+            bgm_loop: Some(args.bgm_loop),
+            bgm_volume: args.bgm_volume,
+            bgm_fade_in: args.bgm_fade_in,
+            bgm_fade_out: args.bgm_fade_out,
+            tts: args.tts.clone(),
+            tts_command: args.tts_command.clone(),
+            font_location: args.font_location.clone(),
+            overwrite: Some(args.overwrite),
+            resolution: args.resolution.clone(),
+            fps: Some(args.fps),
+            fontsize: Some(args.fontsize),
+            fontsize_small: Some(args.fontsize_small),
+            long_word_threshold: Some(args.long_word_threshold),
+            font_size_auto: Some(args.font_size_auto),
+            margin: args.margin,
+            letter_spacing: Some(args.letter_spacing),
+            line_spacing: Some(args.line_spacing),
+            vertical: Some(args.vertical),
+            align: Some(args.align),
+            word_x: args.word_x.clone(),
+            renderer: Some(args.renderer),
+            ass_word_threshold: Some(args.ass_word_threshold),
+            vcodec: Some(args.vcodec.clone()),
+            preset: args.preset.clone(),
+            crf: args.crf,
+            pix_fmt: Some(args.pix_fmt.clone()),
+            acodec: Some(args.acodec.clone()),
+            abitrate: Some(args.abitrate.clone()),
+            jobs: Some(args.jobs),
+            hwenc: Some(args.hwenc),
+            hwaccel: Some(args.hwaccel.clone()),
+        }
+    }
+}
+
+// Comments written above each key by `config init`
+const FIELD_DOCS: &[(&str, &str)] = &[
+    (
+        "input_encoding",
+        "Encoding of piped or file input, e.g. gbk or shift_jis",
+    ),
+    ("wpm", "Words per minute"),
+    ("spw", "Fixed seconds per word, overriding wpm"),
+    ("theme", "Color preset: dark, light, sepia or high-contrast"),
+    (
+        "text_color",
+        "Word color (named, #RRGGBB[AA] or rgb()/rgba())",
+    ),
+    ("bg_color", "Background color"),
+    (
+        "bg_gradient",
+        "Bottom color of a gradient starting at bg_color",
+    ),
+    ("vignette", "Darken the corners of the background"),
+    ("blur_background", "Gaussian blur sigma for the background"),
+    ("background_image", "Still image drawn behind the words"),
+    ("focus_color", "Focus line color (default: secondary_color)"),
+    (
+        "secondary_color",
+        "Color of the wpm label, and of the focus lines without focus_color",
+    ),
+    ("countdown", "Seconds of countdown before the first word"),
+    ("outro_text", "Text held centered after the last word"),
+    ("outro_duration", "Seconds to hold the outro text"),
+    (
+        "max_duration",
+        "Refuse to render videos longer than this (seconds)",
+    ),
+    ("chunk_size", "Words shown together per frame"),
+    ("rest_duration", "Pause in seconds after each sentence"),
+    (
+        "word_gap",
+        "Blank seconds at the end of each word's display time",
+    ),
+    (
+        "comma_pause",
+        "Pause after commas, as a multiple of rest_duration",
+    ),
+    (
+        "sentence_pause",
+        "Pause after sentences, as a multiple of rest_duration",
+    ),
+    (
+        "paragraph_pause",
+        "Pause between paragraphs, as a multiple of rest_duration",
+    ),
+    (
+        "periodic_rest_interval",
+        "Insert a pause at least this often (seconds)",
+    ),
+    (
+        "abbreviations",
+        "Extra abbreviations that don't end a sentence",
+    ),
+    (
+        "jieba_dict",
+        "Jieba user dictionary for Chinese segmentation",
+    ),
+    ("strip_markdown", "Remove Markdown syntax from the input"),
+    ("no_cjk", "Skip Jieba and split CJK text on word bounds"),
+    (
+        "max_word_length",
+        "Words longer than this many characters are split",
+    ),
+    ("focus_lines", "Show focus lines around the word"),
+    ("focus_thickness", "Focus line thickness in pixels"),
+    (
+        "focus_offset",
+        "Focus line distance from the top/bottom edge (fraction of height)",
+    ),
+    ("orp", "Align words on their optimal recognition point"),
+    ("pivot_color", "Pivot letter color in orp mode"),
+    ("auto_emphasize", "Highlight long, uncommon words"),
+    ("emphasis_color", "Color of highlighted words"),
+    (
+        "emphasis_min_length",
+        "Letters a word needs to be highlighted",
+    ),
+    ("no_emphasis_stoplist", "Also highlight common long words"),
+    ("context_words", "Show the previous and next word dimmed"),
+    (
+        "show_progress_overlay",
+        "Draw a progress bar along the bottom edge",
+    ),
+    ("context_color", "Color of the context words"),
+    ("watermark", "Corner label: none, wpm or custom"),
+    ("watermark_text", "Label text for the custom watermark"),
+    (
+        "watermark_position",
+        "Watermark corner: top-left, top-right, bottom-left or bottom-right",
+    ),
+    ("bgm_location", "Background music files, played in order"),
+    ("bgm_shuffle", "Shuffle the background music playlist"),
+    (
+        "bgm_loop",
+        "Extra plays of the background music (-1 loops forever)",
+    ),
+    ("bgm_volume", "Background music volume from 0.0 to 1.0"),
+    ("bgm_fade_in", "Background music fade-in in seconds"),
+    ("bgm_fade_out", "Background music fade-out in seconds"),
+    (
+        "tts",
+        "Narration engine: espeak, say, or a name used with tts_command",
+    ),
+    (
+        "tts_command",
+        "Narration command template with {text} and {out} placeholders",
+    ),
+    ("font_location", "Font file used for the words"),
+    ("overwrite", "Overwrite the output file if it exists"),
+    ("resolution", "Output resolution as WIDTHxHEIGHT"),
+    ("fps", "Output frame rate"),
+    ("fontsize", "Font size for regular words"),
+    ("fontsize_small", "Font size for long words"),
+    (
+        "long_word_threshold",
+        "Character count above which fontsize_small is used",
+    ),
+    (
+        "font_size_auto",
+        "Shrink words that would overflow the frame",
+    ),
+    ("margin", "Horizontal padding in pixels for font_size_auto"),
+    ("letter_spacing", "Thin spaces inserted between letters"),
+    ("line_spacing", "drawtext line spacing in pixels"),
+    ("vertical", "Stack CJK characters top to bottom"),
+    ("align", "Word position: left, center or right"),
+    ("word_x", "drawtext x expression for the word"),
+    ("renderer", "Word renderer: auto, drawtext or ass"),
+    (
+        "ass_word_threshold",
+        "Word count above which the auto renderer uses ass",
+    ),
+    ("vcodec", "Video encoder"),
+    ("preset", "Encoder preset"),
+    ("crf", "Constant rate factor"),
+    ("pix_fmt", "Pixel format, e.g. yuv420p"),
+    ("acodec", "Audio encoder for BGM and narration"),
+    ("abitrate", "Audio bitrate, e.g. 128k"),
+    ("jobs", "Parallel FFmpeg processes"),
+    ("hwenc", "Hardware encoder selection: none or auto"),
+    ("hwaccel", "Hardware decoding method for FFmpeg's -hwaccel"),
+];
+
+// Prefix each `key = value` line with its description
+fn add_comments(toml: &str) -> String {
+    let mut commented = String::new();
+
+    for line in toml.lines() {
+        let key = line.split('=').next().unwrap_or_default().trim();
+        if let Some((_, doc)) = FIELD_DOCS.iter().find(|(name, _)| *name == key) {
+            commented.push_str(&format!("# {}\n", doc));
+        }
+        commented.push_str(line);
+        commented.push('\n');
+    }
+
+    commented
+}
+
+// Commented default config for `config init`. Defaults come straight from clap so
+// they can't drift; the colors are left out so a `theme` set later takes effect
+fn default_config() -> Result<String> {
+    use clap::Parser;
+    let defaults = crate::Args::parse_from(["src-cli"]);
+    let config = Config {
+        text_color: None,
+        bg_color: None,
+        secondary_color: None,
+        ..Config::from_args(&defaults)
+    };
+    let content = toml::to_string(&config).context("Failed to serialize default config")?;
+
+    Ok(add_comments(&content))
+}
+
+// Config file management subcommands
+#[derive(clap::Subcommand, Debug, Clone)]
+pub enum ConfigAction {
+    /// Write a commented default config file
+    Init {
+        /// Overwrite an existing config file
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print the config file path
+    Path,
+    /// Print the merged configuration
+    Show,
+}
+
+pub fn run_config_action(action: &ConfigAction, args: &crate::Args) -> Result<()> {
+    match action {
+        ConfigAction::Init { force } => {
+            let config_path = get_config_path(args.config.as_deref())?;
+            if config_path.exists() && !force {
+                anyhow::bail!(
+                    "Config already exists at {}. Use --force to overwrite",
+                    config_path.display()
+                );
+            }
+
+            std::fs::write(&config_path, default_config()?)
+                .with_context(|| format!("Failed to write config to {}", config_path.display()))?;
+            println!(
+                "{} Config written: {}",
+                crate::log::done_mark(),
+                config_path.display()
+            );
+        }
+        ConfigAction::Path => {
+            println!("{}", get_config_path(args.config.as_deref())?.display());
+        }
+        ConfigAction::Show => {
+            let content =
+                toml::to_string(&Config::from_args(args)).context("Failed to serialize config")?;
+            print!("{}", content);
+        }
+    }
+
+    Ok(())
+}
+
+fn get_config_path(explicit: Option<&Path>) -> Result<PathBuf> {
+    // An explicit --config always wins
+    if let Some(path) = explicit {
+        return Ok(path.to_path_buf());
+    }
+
     let home = if cfg!(target_os = "windows") {
         std::env::var("USERPROFILE")
             .or_else(|_| {
@@ -30,13 +437,35 @@ fn get_config_path() -> Result<PathBuf> {
         std::env::var("HOME").context("Could not find home directory")?
     };
 
+    // Prefer $XDG_CONFIG_HOME/src-cli/config.toml, then ~/.config/src-cli/config.toml
+    if !cfg!(target_os = "windows") {
+        let xdg_config_home = std::env::var("XDG_CONFIG_HOME")
+            .ok()
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from);
+        let xdg_path = xdg_config_home
+            .into_iter()
+            .chain([PathBuf::from(&home).join(".config")])
+            .map(|dir| dir.join("src-cli").join("config.toml"))
+            .find(|path| path.exists());
+
+        if let Some(path) = xdg_path {
+            return Ok(path);
+        }
+    }
+
+    // Legacy location, kept for backward compatibility
     Ok(PathBuf::from(home).join(".src-cli.toml"))
 }
 
-pub fn load_config() -> Result<Config> {
-    let config_path = get_config_path()?;
+pub fn load_config(explicit: Option<&Path>) -> Result<Config> {
+    let config_path = get_config_path(explicit)?;
 
     if !config_path.exists() {
+        // Only the implicit home config is allowed to be missing
+        if explicit.is_some() {
+            anyhow::bail!("Config file not found: {}", config_path.display());
+        }
         return Ok(Config::default());
     }
 
@@ -49,48 +478,524 @@ pub fn load_config() -> Result<Config> {
     Ok(config)
 }
 
-pub fn merge_config_with_args(args: &mut crate::Args) -> Result<()> {
+pub fn merge_config_with_args(args: &mut crate::Args, matches: &ArgMatches) -> Result<()> {
     // Load config and merge with CLI args (CLI args take precedence)
-    let mut config = load_config().context("Failed to load user configuration")?;
-    // Only override if arg is at default value and config has a value
-
-    // Scalar fields - use a helper function
-    fn merge_scalar<T: PartialEq>(target: &mut T, default: T, source: Option<T>) {
-        if *target == default
-            && let Some(value) = source
-        {
+    let config =
+        load_config(args.config.as_deref()).context("Failed to load user configuration")?;
+    apply_config(args, matches, config);
+
+    Ok(())
+}
+
+fn apply_config(args: &mut crate::Args, matches: &ArgMatches, mut config: Config) {
+    // Only override if the user didn't pass the flag and config has a value
+    let provided = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+    fn merge<T>(target: &mut T, provided: bool, source: Option<T>) {
+        if !provided && let Some(value) = source {
             *target = value;
         }
     }
 
-    merge_scalar(&mut args.wpm, 300, config.wpm);
-    merge_scalar(&mut args.text_color, "white".to_string(), config.text_color);
-    merge_scalar(&mut args.bg_color, "black".to_string(), config.bg_color);
-    merge_scalar(
+    merge(&mut args.wpm, provided("wpm"), config.wpm);
+    merge(&mut args.fps, provided("fps"), config.fps);
+    merge(&mut args.fontsize, provided("fontsize"), config.fontsize);
+    merge(
+        &mut args.fontsize_small,
+        provided("fontsize_small"),
+        config.fontsize_small,
+    );
+    merge(
+        &mut args.long_word_threshold,
+        provided("long_word_threshold"),
+        config.long_word_threshold,
+    );
+    merge(
+        &mut args.font_size_auto,
+        provided("font_size_auto"),
+        config.font_size_auto,
+    );
+    merge(&mut args.vertical, provided("vertical"), config.vertical);
+    merge(&mut args.align, provided("align"), config.align);
+    // --align on the command line beats a --word-x expression from the config
+    if provided("align") {
+        config.word_x = None;
+    }
+    args.word_x = args.word_x.take().or(config.word_x);
+    merge(&mut args.renderer, provided("renderer"), config.renderer);
+    merge(&mut args.watermark, provided("watermark"), config.watermark);
+    merge(
+        &mut args.watermark_position,
+        provided("watermark_position"),
+        config.watermark_position,
+    );
+    merge(
+        &mut args.ass_word_threshold,
+        provided("ass_word_threshold"),
+        config.ass_word_threshold,
+    );
+    // A theme fills in the colors that weren't given explicitly. One from the command
+    // line also replaces the config's colors; one from the config only fills its gaps
+    if provided("theme") {
+        config.text_color = None;
+        config.bg_color = None;
+        config.secondary_color = None;
+    }
+    args.theme = args.theme.or(config.theme);
+    if let Some(theme) = args.theme {
+        let (text, bg, secondary) = theme.colors();
+        for (color, id, value) in [
+            (&mut args.text_color, "text_color", text),
+            (&mut args.bg_color, "bg_color", bg),
+            (&mut args.secondary_color, "secondary_color", secondary),
+        ] {
+            if !provided(id) {
+                *color = value.to_string();
+            }
+        }
+    }
+    merge(
+        &mut args.text_color,
+        provided("text_color"),
+        config.text_color,
+    );
+    merge(&mut args.bg_color, provided("bg_color"), config.bg_color);
+    merge(
         &mut args.secondary_color,
-        "#1a1911".to_string(),
+        provided("secondary_color"),
         config.secondary_color,
     );
+    merge(&mut args.countdown, provided("countdown"), config.countdown);
+    merge(
+        &mut args.outro_duration,
+        provided("outro_duration"),
+        config.outro_duration,
+    );
+    merge(
+        &mut args.strip_markdown,
+        provided("strip_markdown"),
+        config.strip_markdown,
+    );
+    merge(&mut args.no_cjk, provided("no_cjk"), config.no_cjk);
+    merge(
+        &mut args.max_word_length,
+        provided("max_word_length"),
+        config.max_word_length,
+    );
+    merge(
+        &mut args.auto_emphasize,
+        provided("auto_emphasize"),
+        config.auto_emphasize,
+    );
+    merge(
+        &mut args.emphasis_color,
+        provided("emphasis_color"),
+        config.emphasis_color,
+    );
+    merge(
+        &mut args.emphasis_min_length,
+        provided("emphasis_min_length"),
+        config.emphasis_min_length,
+    );
+    merge(
+        &mut args.no_emphasis_stoplist,
+        provided("no_emphasis_stoplist"),
+        config.no_emphasis_stoplist,
+    );
+    merge(
+        &mut args.context_words,
+        provided("context_words"),
+        config.context_words,
+    );
+    merge(
+        &mut args.show_progress_overlay,
+        provided("show_progress_overlay"),
+        config.show_progress_overlay,
+    );
+    merge(&mut args.overwrite, provided("overwrite"), config.overwrite);
+    merge(
+        &mut args.chunk_size,
+        provided("chunk_size"),
+        config.chunk_size,
+    );
+    merge(
+        &mut args.rest_duration,
+        provided("rest_duration"),
+        config.rest_duration,
+    );
+    merge(&mut args.word_gap, provided("word_gap"), config.word_gap);
+    merge(
+        &mut args.comma_pause,
+        provided("comma_pause"),
+        config.comma_pause,
+    );
+    merge(
+        &mut args.sentence_pause,
+        provided("sentence_pause"),
+        config.sentence_pause,
+    );
+    merge(
+        &mut args.paragraph_pause,
+        provided("paragraph_pause"),
+        config.paragraph_pause,
+    );
+    merge(
+        &mut args.focus_thickness,
+        provided("focus_thickness"),
+        config.focus_thickness,
+    );
+    merge(
+        &mut args.focus_offset,
+        provided("focus_offset"),
+        config.focus_offset,
+    );
+    merge(&mut args.orp, provided("orp"), config.orp);
+    merge(
+        &mut args.periodic_rest_interval,
+        provided("periodic_rest_interval"),
+        config.periodic_rest_interval,
+    );
+    merge(
+        &mut args.abbreviations,
+        provided("abbreviations"),
+        config.abbreviations,
+    );
+    // The last of --focus-lines/--no-focus-lines wins; without either the config decides
+    if provided("no_focus_lines") {
+        args.focus_lines = false;
+    } else {
+        merge(
+            &mut args.focus_lines,
+            provided("focus_lines"),
+            config.focus_lines,
+        );
+    }
+    merge(
+        &mut args.letter_spacing,
+        provided("letter_spacing"),
+        config.letter_spacing,
+    );
+    merge(
+        &mut args.line_spacing,
+        provided("line_spacing"),
+        config.line_spacing,
+    );
+    merge(&mut args.vignette, provided("vignette"), config.vignette);
+    args.blur_background = args.blur_background.take().or(config.blur_background);
+    merge(&mut args.vcodec, provided("vcodec"), config.vcodec);
+    merge(&mut args.pix_fmt, provided("pix_fmt"), config.pix_fmt);
+    merge(&mut args.acodec, provided("acodec"), config.acodec);
+    merge(&mut args.abitrate, provided("abitrate"), config.abitrate);
+    merge(&mut args.jobs, provided("jobs"), config.jobs);
+    merge(&mut args.hwenc, provided("hwenc"), config.hwenc);
+    merge(&mut args.hwaccel, provided("hwaccel"), config.hwaccel);
 
-    // Float with epsilon comparison
-    const DEFAULT_REST_DURATION: f64 = 0.5;
-    if (args.rest_duration - DEFAULT_REST_DURATION).abs() < f64::EPSILON
-        && let Some(d) = config.rest_duration.take()
-    {
-        args.rest_duration = d;
+    // Option fields are None unless passed on the command line
+    args.bgm_from_config = !provided("bgm_location") && config.bgm_location.is_some();
+    merge(
+        &mut args.bgm_location,
+        provided("bgm_location"),
+        config.bgm_location,
+    );
+    merge(
+        &mut args.bgm_shuffle,
+        provided("bgm_shuffle"),
+        config.bgm_shuffle,
+    );
+    merge(&mut args.bgm_loop, provided("bgm_loop"), config.bgm_loop);
+    args.bgm_volume = args.bgm_volume.or(config.bgm_volume);
+    args.bgm_fade_in = args.bgm_fade_in.or(config.bgm_fade_in);
+    args.bgm_fade_out = args.bgm_fade_out.or(config.bgm_fade_out);
+    args.jieba_dict = args.jieba_dict.take().or(config.jieba_dict);
+    args.font_location = args.font_location.take().or(config.font_location);
+    args.tts = args.tts.take().or(config.tts);
+    args.tts_command = args.tts_command.take().or(config.tts_command);
+    args.resolution = args.resolution.take().or(config.resolution);
+    args.preset = args.preset.take().or(config.preset);
+    args.crf = args.crf.or(config.crf);
+    args.background_image = args.background_image.take().or(config.background_image);
+    args.bg_gradient = args.bg_gradient.take().or(config.bg_gradient);
+    args.pivot_color = args.pivot_color.take().or(config.pivot_color);
+    args.spw = args.spw.or(config.spw);
+    args.input_encoding = args.input_encoding.take().or(config.input_encoding);
+    args.context_color = args.context_color.take().or(config.context_color);
+    args.focus_color = args.focus_color.take().or(config.focus_color);
+    args.margin = args.margin.or(config.margin);
+    args.outro_text = args.outro_text.take().or(config.outro_text);
+    args.max_duration = args.max_duration.or(config.max_duration);
+    args.watermark_text = args.watermark_text.take().or(config.watermark_text);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{CommandFactory, FromArgMatches};
+
+    fn merge(cli: &[&str], config: Config) -> crate::Args {
+        let matches = crate::Args::command()
+            .get_matches_from(std::iter::once("src-cli").chain(cli.iter().copied()));
+        let mut args = crate::Args::from_arg_matches(&matches).unwrap();
+        apply_config(&mut args, &matches, config);
+        args
     }
 
-    // Boolean
-    if args.focus_lines
-        && let Some(f) = config.focus_lines.take()
-    {
-        args.focus_lines = f;
+    #[test]
+    fn test_rest_duration_from_config() {
+        let args = merge(
+            &[],
+            Config {
+                rest_duration: Some(0.75),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(args.rest_duration, 0.75);
     }
 
-    // Option fields - use get_or_insert
-    args.bgm_location = args.bgm_location.take().or(config.bgm_location);
-    args.font_location = args.font_location.take().or(config.font_location);
-    args.overwrite_output_file = args.overwrite_output_file.or(config.overwrite_output_file);
+    #[test]
+    fn test_rest_duration_cli_wins() {
+        let args = merge(
+            &["--rest-duration", "0.3"],
+            Config {
+                rest_duration: Some(0.75),
+                ..Default::default()
+            },
+        );
 
-    Ok(())
+        assert_eq!(args.rest_duration, 0.3);
+    }
+
+    #[test]
+    fn test_colors_from_config() {
+        let args = merge(
+            &[],
+            Config {
+                text_color: Some("#ff0000".to_string()),
+                bg_color: Some("navy".to_string()),
+                secondary_color: Some("#00ff00".to_string()),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(args.text_color, "#ff0000");
+        assert_eq!(args.bg_color, "navy");
+        assert_eq!(args.secondary_color, "#00ff00");
+    }
+
+    #[test]
+    fn test_colors_cli_wins() {
+        let args = merge(
+            &[
+                "--text-color",
+                "yellow",
+                "--bg-color",
+                "blue",
+                "--secondary-color",
+                "gray",
+            ],
+            Config {
+                text_color: Some("#ff0000".to_string()),
+                bg_color: Some("navy".to_string()),
+                secondary_color: Some("#00ff00".to_string()),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(args.text_color, "yellow");
+        assert_eq!(args.bg_color, "blue");
+        assert_eq!(args.secondary_color, "gray");
+    }
+
+    #[test]
+    fn test_explicit_default_value_wins() {
+        let args = merge(
+            &["--wpm", "300"],
+            Config {
+                wpm: Some(450),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(args.wpm, 300);
+    }
+
+    #[test]
+    fn test_focus_lines_flags_and_config() {
+        let config = |focus_lines| Config {
+            focus_lines: Some(focus_lines),
+            ..Default::default()
+        };
+
+        assert!(merge(&[], Config::default()).focus_lines);
+        assert!(!merge(&[], config(false)).focus_lines);
+        assert!(merge(&["--focus-lines"], config(false)).focus_lines);
+        assert!(!merge(&["--no-focus-lines"], config(true)).focus_lines);
+        assert!(!merge(&["--no-focus-lines"], Config::default()).focus_lines);
+        assert!(merge(&["--no-focus-lines", "--focus-lines"], config(false)).focus_lines);
+        assert!(!merge(&["--focus-lines", "--no-focus-lines"], config(true)).focus_lines);
+        // The flag takes no value, so a following subcommand or argument stays intact
+        let matches = crate::Args::command()
+            .try_get_matches_from(["src-cli", "--focus-lines", "check"])
+            .unwrap();
+        assert_eq!(matches.subcommand_name(), Some("check"));
+    }
+
+    #[test]
+    fn test_explicit_config_missing_errors() {
+        let path = std::env::temp_dir().join("src-cli-missing-config.toml");
+
+        assert!(load_config(Some(&path)).is_err());
+    }
+
+    #[test]
+    fn test_explicit_config_loads_with_cli_precedence() {
+        let path = std::env::temp_dir().join(format!("src-cli-{}.toml", std::process::id()));
+        std::fs::write(&path, "wpm = 450\nbg_color = \"navy\"\n").unwrap();
+
+        let config = load_config(Some(&path));
+        std::fs::remove_file(&path).unwrap();
+
+        let args = merge(&["--wpm", "200"], config.unwrap());
+        assert_eq!(args.wpm, 200);
+        assert_eq!(args.bg_color, "navy");
+    }
+
+    #[test]
+    fn test_bgm_location_single_or_list() {
+        let single: Config = toml::from_str("bgm_location = \"a.webm\"").unwrap();
+        let list: Config = toml::from_str("bgm_location = [\"a.webm\", \"b.webm\"]").unwrap();
+
+        assert_eq!(single.bgm_location, Some(vec!["a.webm".to_string()]));
+        assert_eq!(
+            list.bgm_location,
+            Some(vec!["a.webm".to_string(), "b.webm".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_bgm_source_is_tracked() {
+        let config = || Config {
+            bgm_location: Some(vec!["a.webm".to_string()]),
+            ..Default::default()
+        };
+
+        assert!(merge(&[], config()).bgm_from_config);
+        assert!(!merge(&["--bgm-location", "b.webm"], config()).bgm_from_config);
+        assert!(!merge(&[], Config::default()).bgm_from_config);
+    }
+
+    #[test]
+    fn test_overwrite_from_legacy_key() {
+        let config: Config = toml::from_str("overwrite_output_file = true").unwrap();
+        assert!(merge(&[], config).overwrite);
+        assert!(!merge(&[], Config::default()).overwrite);
+        assert!(merge(&["-y"], Config::default()).overwrite);
+    }
+
+    #[test]
+    fn test_encoder_from_config() {
+        use crate::ffmpeg::HwEncode;
+        let config = || Config {
+            vcodec: Some("libx265".to_string()),
+            hwenc: Some(HwEncode::Auto),
+            ..Default::default()
+        };
+
+        let args = merge(&[], config());
+        assert_eq!(
+            (args.vcodec.as_str(), args.hwenc),
+            ("libx265", HwEncode::Auto)
+        );
+        let args = merge(&["--vcodec", "libvpx-vp9", "--hwenc", "none"], config());
+        assert_eq!(
+            (args.vcodec.as_str(), args.hwenc),
+            ("libvpx-vp9", HwEncode::None)
+        );
+    }
+
+    #[test]
+    fn test_theme_layers_under_explicit_colors() {
+        let colors = |args: &crate::Args| {
+            (
+                args.text_color.clone(),
+                args.bg_color.clone(),
+                args.secondary_color.clone(),
+            )
+        };
+        let config = || Config {
+            text_color: Some("#ff0000".to_string()),
+            ..Default::default()
+        };
+
+        // The CLI theme replaces config colors, but not explicit flags
+        let args = merge(&["--theme", "light", "--bg-color", "navy"], config());
+        assert_eq!(
+            colors(&args),
+            (
+                "#1a1a1a".to_string(),
+                "navy".to_string(),
+                "#d6d6d6".to_string()
+            )
+        );
+
+        // A config theme only fills the colors the config leaves out
+        let args = merge(
+            &[],
+            Config {
+                theme: Some(crate::ffmpeg::Theme::Sepia),
+                ..config()
+            },
+        );
+        assert_eq!(
+            colors(&args),
+            (
+                "#ff0000".to_string(),
+                "#f4ecd8".to_string(),
+                "#d9c9a8".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_theme_in_init_config_changes_colors() {
+        let mut config: Config = toml::from_str(&default_config().unwrap()).unwrap();
+        assert!(config.text_color.is_none() && config.bg_color.is_none());
+
+        config.theme = Some(crate::ffmpeg::Theme::Sepia);
+        let args = merge(&[], config);
+        assert_eq!(
+            (args.text_color, args.bg_color, args.secondary_color),
+            (
+                "#5b4636".to_string(),
+                "#f4ecd8".to_string(),
+                "#d9c9a8".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_align_and_word_x_layering() {
+        let config = |align, word_x: &str| Config {
+            align,
+            word_x: Some(word_x.to_string()),
+            ..Default::default()
+        };
+        let right = Some(crate::ffmpeg::Align::Right);
+
+        // A custom config expression wins over the config align
+        let args = merge(&[], config(right, "w*0.3"));
+        assert_eq!(args.word_x.as_deref(), Some("w*0.3"));
+        // Even when it spells out the default position
+        let args = merge(&[], config(right, "(w-text_w)/5*2"));
+        assert_eq!(args.word_x.as_deref(), Some("(w-text_w)/5*2"));
+        // `config init` leaves it out, so a config align applies
+        let init: Config = toml::from_str(&default_config().unwrap()).unwrap();
+        assert_eq!(init.word_x, None);
+        // --align on the command line beats the config expression
+        let args = merge(&["--align", "center"], config(None, "w*0.3"));
+        assert_eq!(args.word_x, None);
+        // An explicit --word-x always wins
+        let args = merge(&["--word-x", "(w-text_w)/5*2"], config(right, "w*0.3"));
+        assert_eq!(args.word_x.as_deref(), Some("(w-text_w)/5*2"));
+    }
 }
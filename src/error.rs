@@ -0,0 +1,61 @@
+use thiserror::Error as ThisError;
+
+/// Errors returned by [`generate`](crate::generate) and the validation functions.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error(
+        "FFmpeg is not installed or not found in PATH. Please install FFmpeg first.\nVisit: https://ffmpeg.org/download.html"
+    )]
+    FfmpegMissing,
+
+    #[error("Invalid color '{color}'. {reason}")]
+    InvalidColor { color: String, reason: String },
+
+    #[error("Font file not found: {0}. Provide a valid font via --font-location")]
+    FontNotFound(String),
+
+    /// The text, input file, or piped input had no words
+    #[error("The {0} was empty")]
+    EmptyInput(String),
+
+    /// BGM files passed explicitly that don't exist
+    #[error("BGM file not found: {}", .0.join(", "))]
+    BgmMissing(Vec<String>),
+
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl Error {
+    pub(crate) fn invalid_color(color: &str, reason: impl Into<String>) -> Self {
+        Error::InvalidColor {
+            color: color.to_string(),
+            reason: reason.into(),
+        }
+    }
+}
+
+// Internal code uses anyhow for context; recover the typed error underneath when there is one
+impl From<anyhow::Error> for Error {
+    fn from(err: anyhow::Error) -> Self {
+        err.downcast::<Error>().unwrap_or_else(Error::Other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_from_anyhow_recovers_typed_error() {
+        let err = Err::<(), _>(Error::FontNotFound("a.ttf".into()))
+            .context("Invalid font")
+            .unwrap_err();
+        assert!(matches!(Error::from(err), Error::FontNotFound(path) if path == "a.ttf"));
+
+        let err = Error::from(anyhow::anyhow!("boom"));
+        assert!(matches!(err, Error::Other(_)));
+        assert_eq!(err.to_string(), "boom");
+    }
+}
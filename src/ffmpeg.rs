@@ -1,103 +1,550 @@
-use std::io::{self, IsTerminal, Read};
-use std::path::Path;
-use std::process::Command;
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
 use std::time::Instant;
 
 use anyhow::{Context, Result, bail};
+use once_cell::sync::OnceCell;
 use os_info::Type;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::Error;
+use crate::log;
+
+mod ass;
+mod epub;
+mod segments;
+mod srt;
+pub mod text;
+mod tts;
+use text::{
+    Token, has_cjk, is_emphasized, is_rtl, load_jieba, split_at_orp, split_long_words, split_text,
+    strip_markdown,
+};
+
+// Hardware encoder selection
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum HwEncode {
+    // Use --vcodec as given
+    #[default]
+    None,
+    // Probe FFmpeg for a hardware H.264 encoder
+    Auto,
+}
+
+// How the words are drawn onto the video
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Renderer {
+    // ASS subtitles above --ass-word-threshold words, drawtext otherwise
+    #[default]
+    Auto,
+    // One drawtext filter per word
+    Drawtext,
+    // A single subtitles filter burning a generated ASS file
+    Ass,
+}
+
+// Corner label drawn over the video
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Watermark {
+    // No label
+    None,
+    // "N wpm"
+    #[default]
+    Wpm,
+    // --watermark-text
+    Custom,
+}
+
+// Corner for the watermark
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum WatermarkPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+impl WatermarkPosition {
+    // drawtext x/y expressions, 10% in from the corner's edges
+    fn coordinates(self) -> (&'static str, &'static str) {
+        match self {
+            WatermarkPosition::TopLeft => ("(w-text_w)*0.1", "(h-text_h)*0.1"),
+            WatermarkPosition::TopRight => ("(w-text_w)*0.9", "(h-text_h)*0.1"),
+            WatermarkPosition::BottomLeft => ("(w-text_w)*0.1", "(h-text_h)*0.9"),
+            WatermarkPosition::BottomRight => ("(w-text_w)*0.9", "(h-text_h)*0.9"),
+        }
+    }
+}
+
+// Horizontal placement of the word, a shorthand for the common --word-x expressions
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum Align {
+    // Two fifths of the free space on the left, where the eye rests after a line break
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+impl Align {
+    fn x_expression(self) -> &'static str {
+        match self {
+            Align::Left => DEFAULT_WORD_X,
+            Align::Center => "(w-text_w)/2",
+            Align::Right => "(w-text_w)*0.9",
+        }
+    }
+
+    // ASS alignment (\an) and x position; the right anchor sits where a
+    // typical word ends with the drawtext expression
+    pub(crate) fn ass_position(self, width: u32) -> (u8, u32) {
+        match self {
+            Align::Left => (5, width * 2 / 5),
+            Align::Center => (5, width / 2),
+            Align::Right => (6, width * 9 / 10),
+        }
+    }
+}
+
+// Named color presets for the words, background and focus lines
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Theme {
+    Dark,
+    Light,
+    Sepia,
+    HighContrast,
+}
+
+impl Theme {
+    // (text, background, secondary) colors; add a variant and its row to extend
+    pub(crate) fn colors(self) -> (&'static str, &'static str, &'static str) {
+        match self {
+            Theme::Dark => ("#ffffee", "black", "#1a1911"),
+            Theme::Light => ("#1a1a1a", "#fafafa", "#d6d6d6"),
+            Theme::Sepia => ("#5b4636", "#f4ecd8", "#d9c9a8"),
+            Theme::HighContrast => ("white", "black", "yellow"),
+        }
+    }
+}
+
+// Output container, from --format or inferred from the --output extension
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Container {
+    // .mp4, .m4v, .mov
+    Mp4,
+    // .mkv accepts any encoder
+    Mkv,
+    // .webm only holds VP8/VP9/AV1 video and Vorbis/Opus audio
+    Webm,
+    // Silent animated GIF with a generated palette
+    Gif,
+}
+
+// GIFs get large and slow to decode at video frame rates
+const GIF_MAX_FPS: u32 = 15;
+
+impl Container {
+    fn resolve(format: Option<Container>, output: &str) -> Result<Self> {
+        match format {
+            Some(format) => Ok(format),
+            None => Container::from_output(output),
+        }
+    }
 
-mod text;
-use text::split_text;
+    fn from_output(output: &str) -> Result<Self> {
+        let extension = Path::new(output)
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        match extension.as_str() {
+            "mp4" | "m4v" | "mov" => Ok(Container::Mp4),
+            "mkv" => Ok(Container::Mkv),
+            "webm" => Ok(Container::Webm),
+            "gif" => Ok(Container::Gif),
+            "" => bail!(
+                "Output '{}' has no extension. Use .mp4, .mov, .mkv, .webm or .gif, or pass --format",
+                output
+            ),
+            other => bail!(
+                "Unsupported output format '.{}'. Use .mp4, .mov, .mkv, .webm or .gif, or pass --format",
+                other
+            ),
+        }
+    }
+
+    fn supports_vcodec(self, vcodec: &str) -> bool {
+        match self {
+            Container::Webm => ["vp8", "vp9", "libvpx", "av1"]
+                .iter()
+                .any(|family| vcodec.contains(family)),
+            // The encoder is ignored, GIFs always use FFmpeg's gif encoder
+            Container::Mp4 | Container::Mkv | Container::Gif => true,
+        }
+    }
+
+    fn supports_acodec(self, acodec: &str) -> bool {
+        match self {
+            Container::Webm => ["opus", "vorbis"]
+                .iter()
+                .any(|family| acodec.contains(family)),
+            Container::Mp4 | Container::Mkv | Container::Gif => true,
+        }
+    }
+
+    // FFmpeg muxer name; the temporary output's extension doesn't tell FFmpeg
+    fn muxer(self) -> &'static str {
+        match self {
+            Container::Mp4 => "mp4",
+            Container::Mkv => "matroska",
+            Container::Webm => "webm",
+            Container::Gif => "gif",
+        }
+    }
+
+    fn audio_codec(self) -> &'static str {
+        match self {
+            Container::Webm => "libopus",
+            Container::Mp4 | Container::Mkv | Container::Gif => "aac",
+        }
+    }
+}
 
 pub fn check_ffmpeg() -> Result<()> {
-    let output = Command::new("ffmpeg")
-        .arg("-version")
-        .output()
-        .context("Failed to execute ffmpeg command")?;
+    let output = match Command::new("ffmpeg").arg("-version").output() {
+        Ok(output) => output,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(Error::FfmpegMissing.into());
+        }
+        Err(e) => return Err(e).context("Failed to execute ffmpeg command"),
+    };
 
     if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
         bail!(
-            "FFmpeg is not installed or not found in PATH. Please install FFmpeg first.\nVisit: https://ffmpeg.org/download.html"
+            "FFmpeg was found but `ffmpeg -version` failed ({}):\n{}",
+            output.status,
+            stderr.trim()
         );
     }
 
     // Use idiomatic code structure
     let version_output = String::from_utf8_lossy(&output.stdout);
     if let Some(first_line) = version_output.lines().next() {
-        println!("FFmpeg found: {}", first_line);
+        info!("FFmpeg found: {}", first_line);
     }
 
     Ok(())
 }
-// Validate FFmpeg color format
-fn validate_color(color: &str) -> Result<()> {
+// Named colors accepted by validate_color, with their RGB values
+const NAMED_COLORS: &[(&str, u32)] = &[
+    ("black", 0x000000),
+    ("white", 0xFFFFFF),
+    ("red", 0xFF0000),
+    ("green", 0x008000),
+    ("blue", 0x0000FF),
+    ("yellow", 0xFFFF00),
+    ("cyan", 0x00FFFF),
+    ("magenta", 0xFF00FF),
+    ("orange", 0xFFA500),
+    ("purple", 0x800080),
+    ("pink", 0xFFC0CB),
+    ("brown", 0xA52A2A),
+    ("gray", 0x808080),
+    ("grey", 0x808080),
+    ("silver", 0xC0C0C0),
+    ("gold", 0xFFD700),
+    ("lime", 0x00FF00),
+    ("navy", 0x000080),
+    ("teal", 0x008080),
+    ("olive", 0x808000),
+    ("maroon", 0x800000),
+    ("aqua", 0x00FFFF),
+    ("fuchsia", 0xFF00FF),
+    ("darkred", 0x8B0000),
+    ("darkgreen", 0x006400),
+    ("darkblue", 0x00008B),
+    ("lightred", 0xFF7F7F),
+    ("lightgreen", 0x90EE90),
+    ("lightblue", 0xADD8E6),
+    ("darkgray", 0xA9A9A9),
+    ("darkgrey", 0xA9A9A9),
+    ("lightgray", 0xD3D3D3),
+    ("lightgrey", 0xD3D3D3),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+];
+
+// Hardware encoders tried by --hwenc auto, in order of preference
+const HW_ENCODERS: &[&str] = &["h264_nvenc", "h264_qsv", "h264_videotoolbox", "h264_vaapi"];
+
+// Encoders reported by `ffmpeg -encoders`, probed once
+static AVAILABLE_ENCODERS: OnceCell<Vec<String>> = OnceCell::new();
+
+fn available_encoders() -> &'static [String] {
+    AVAILABLE_ENCODERS.get_or_init(|| {
+        let Ok(output) = Command::new("ffmpeg")
+            .args(["-hide_banner", "-encoders"])
+            .output()
+        else {
+            return Vec::new();
+        };
+
+        // Lines look like " V....D h264_nvenc  NVIDIA NVENC H.264 encoder"
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| line.split_whitespace().nth(1))
+            .map(str::to_string)
+            .collect()
+    })
+}
+
+// FFmpeg errors that point at the encoder or hardware setup rather than the input
+const ENCODER_ERRORS: &[&str] = &[
+    "Error while opening encoder",
+    "Could not open encoder",
+    "Unknown encoder",
+    "No capable devices found",
+    "No NVENC capable devices",
+    "Cannot load",
+    "Device creation failed",
+    "Failed to initialise VAAPI",
+    "Error initializing output stream",
+    "Hardware device setup failed",
+];
+
+fn is_encoder_failure(stderr: &str) -> bool {
+    ENCODER_ERRORS.iter().any(|error| stderr.contains(error))
+}
+
+// Pick the first available hardware H.264 encoder, falling back to libx264
+fn detect_hw_encoder() -> &'static str {
+    let encoders = available_encoders();
+
+    HW_ENCODERS
+        .iter()
+        .find(|&&encoder| encoders.iter().any(|e| e == encoder))
+        .copied()
+        .unwrap_or("libx264")
+}
+
+/// Validate an FFmpeg color: a name, #RRGGBB[AA], 0xRRGGBB[AA], rgb() or rgba()
+pub fn validate_color(color: &str) -> Result<(), Error> {
     let color_lower = color.to_lowercase();
 
     // Check hex colors
     if color.starts_with('#') || color.starts_with("0x") {
         let hex_part = color.trim_start_matches('#').trim_start_matches("0x");
-        if hex_part.len() == 6 && hex_part.chars().all(|c| c.is_ascii_hexdigit()) {
+        if matches!(hex_part.len(), 6 | 8) && hex_part.chars().all(|c| c.is_ascii_hexdigit()) {
             return Ok(());
         }
-        bail!("Invalid hex color format. Use #RRGGBB or 0xRRGGBB (e.g., #FF0000)");
+        return Err(Error::invalid_color(
+            color,
+            "Use 6 hex digits (#RRGGBB) or 8 digits with alpha (#RRGGBBAA), e.g., #FF0000 or #FF000080",
+        ));
     }
 
-    // Check RGB format
-    if color_lower.starts_with("rgb(") && color_lower.ends_with(')') {
-        return Ok(());
+    // Check RGB/RGBA format
+    if let Some(inner) = color_lower
+        .strip_prefix("rgb(")
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        return validate_rgb_components(color, inner, false);
+    }
+    if let Some(inner) = color_lower
+        .strip_prefix("rgba(")
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        return validate_rgb_components(color, inner, true);
     }
 
-    const VALID_COLORS: &[&str] = &[
-        "black",
-        "white",
-        "red",
-        "green",
-        "blue",
-        "yellow",
-        "cyan",
-        "magenta",
-        "orange",
-        "purple",
-        "pink",
-        "brown",
-        "gray",
-        "grey",
-        "silver",
-        "gold",
-        "lime",
-        "navy",
-        "teal",
-        "olive",
-        "maroon",
-        "aqua",
-        "fuchsia",
-        "darkred",
-        "darkgreen",
-        "darkblue",
-        "lightred",
-        "lightgreen",
-        "lightblue",
-        "darkgray",
-        "darkgrey",
-        "lightgray",
-        "lightgrey",
-        "dimgray",
-        "dimgrey",
-    ];
-
-    if VALID_COLORS.contains(&color_lower.as_str()) {
+    if NAMED_COLORS.iter().any(|(name, _)| *name == color_lower) {
         return Ok(());
     }
 
-    bail!(
-        "Invalid color '{}'. Use:\n  - Named colors (e.g., white, black, red, blue)\n  - Hex colors (e.g., #FF0000 or 0xFF0000)\n  - RGB format (e.g., rgb(255,0,0))",
-        color
-    );
+    Err(Error::invalid_color(
+        color,
+        "Use:\n  - Named colors (e.g., white, black, red, blue)\n  - Hex colors (e.g., #FF0000, 0xFF0000 or #FF000080 with alpha)\n  - RGB format (e.g., rgb(255,0,0) or rgba(255,0,0,0.5))",
+    ))
+}
+
+// Accept drawtext x expressions built from numbers, variables, functions and
+// arithmetic. The value is quoted in the filter, so commas are fine but quotes
+// and ':' would break out of it
+fn validate_x_expression(expr: &str) -> Result<()> {
+    if expr.trim().is_empty() {
+        bail!("Invalid x expression. Use e.g. (w-text_w)/2");
+    }
+
+    if let Some(c) = expr
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || "_.+-*/(), ".contains(*c)))
+    {
+        bail!(
+            "Invalid x expression '{}'. Character '{}' is not allowed",
+            expr,
+            c
+        );
+    }
+
+    let mut depth = 0i32;
+    for c in expr.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ => {}
+        }
+        if depth < 0 {
+            break;
+        }
+    }
+    if depth != 0 {
+        bail!("Invalid x expression '{}'. Unbalanced parentheses", expr);
+    }
+
+    Ok(())
+}
+
+// Validate the comma-separated channels of rgb()/rgba()
+fn validate_rgb_components(color: &str, inner: &str, has_alpha: bool) -> Result<(), Error> {
+    let parts: Vec<&str> = inner.split(',').map(str::trim).collect();
+    let expected = if has_alpha { 4 } else { 3 };
+
+    if parts.len() != expected {
+        return Err(Error::invalid_color(
+            color,
+            format!("Expected {} components, found {}", expected, parts.len()),
+        ));
+    }
+
+    for channel in &parts[..3] {
+        if !channel.parse::<u16>().is_ok_and(|value| value <= 255) {
+            return Err(Error::invalid_color(
+                color,
+                format!(
+                    "Color channel '{}' must be an integer from 0 to 255",
+                    channel
+                ),
+            ));
+        }
+    }
+
+    if has_alpha
+        && !parts[3]
+            .parse::<f64>()
+            .is_ok_and(|alpha| (0.0..=1.0).contains(&alpha))
+    {
+        return Err(Error::invalid_color(
+            color,
+            format!("Alpha '{}' must be a number from 0.0 to 1.0", parts[3]),
+        ));
+    }
+
+    Ok(())
+}
+
+// Resolve a validated color to RGB components (used where FFmpeg syntax can't be passed through)
+fn color_to_rgb(color: &str) -> Option<(u8, u8, u8)> {
+    let color_lower = color.to_lowercase();
+
+    let value = if color.starts_with('#') || color.starts_with("0x") {
+        let hex_part = color.trim_start_matches('#').trim_start_matches("0x");
+        u32::from_str_radix(hex_part.get(..6)?, 16).ok()?
+    } else if let Some(inner) = color_lower
+        .strip_prefix("rgb(")
+        .or_else(|| color_lower.strip_prefix("rgba("))
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        // Alpha (if any) is ignored
+        let channels: Vec<u8> = inner
+            .split(',')
+            .take(3)
+            .map(|part| part.trim().parse::<u8>().ok())
+            .collect::<Option<_>>()?;
+        let [r, g, b] = channels[..] else {
+            return None;
+        };
+        return Some((r, g, b));
+    } else {
+        NAMED_COLORS
+            .iter()
+            .find(|(name, _)| *name == color_lower)
+            .map(|(_, value)| *value)?
+    };
+
+    Some(((value >> 16) as u8, (value >> 8) as u8, value as u8))
+}
+
+// Output frame size in pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Resolution {
+    width: u32,
+    height: u32,
+}
+
+impl Default for Resolution {
+    fn default() -> Self {
+        Self {
+            width: 1920,
+            height: 1080,
+        }
+    }
+}
+
+impl std::fmt::Display for Resolution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+// Parse WIDTHxHEIGHT (e.g., 1080x1920)
+fn parse_resolution(value: &str) -> Result<Resolution> {
+    let Some((width, height)) = value
+        .trim()
+        .to_lowercase()
+        .split_once('x')
+        .map(|(w, h)| (w.trim().parse::<u32>(), h.trim().parse::<u32>()))
+    else {
+        bail!(
+            "Invalid resolution '{}'. Use WIDTHxHEIGHT (e.g., 1920x1080)",
+            value
+        );
+    };
+
+    let (Ok(width), Ok(height)) = (width, height) else {
+        bail!(
+            "Invalid resolution '{}'. Width and height must be numbers (e.g., 1920x1080)",
+            value
+        );
+    };
+
+    if width == 0 || height == 0 {
+        bail!(
+            "Invalid resolution '{}'. Width and height must be greater than 0",
+            value
+        );
+    }
+
+    Ok(Resolution { width, height })
 }
 
-fn get_piped_input() -> anyhow::Result<String> {
+fn get_piped_input(encoding: Option<&str>) -> anyhow::Result<String> {
     #[cfg(windows)]
-    println!("use cmd if encoding is wrong");
+    if encoding.is_none() {
+        info!("If the text looks garbled, pass its encoding, e.g. --input-encoding gbk");
+    }
 
     let stdin = io::stdin();
 
@@ -111,15 +558,56 @@ fn get_piped_input() -> anyhow::Result<String> {
         .read_to_end(&mut buffer)
         .context("Failed to read from stdin")?;
 
-    let content = String::from_utf8_lossy(&buffer).to_string();
+    let content = decode_input(&buffer, encoding)?;
 
     if content.trim().is_empty() {
-        bail!("The piped input was empty.");
+        return Err(Error::EmptyInput("piped input".to_string()).into());
     }
 
     Ok(content)
 }
 
+fn get_file_input(path: &Path, encoding: Option<&str>) -> Result<String> {
+    if !path.exists() {
+        bail!("Input file not found: {}", path.display());
+    }
+
+    let buffer = std::fs::read(path)
+        .with_context(|| format!("Failed to read input file {}", path.display()))?;
+
+    let content = decode_input(&buffer, encoding)?;
+
+    if content.trim().is_empty() {
+        return Err(Error::EmptyInput(format!("input file {}", path.display())).into());
+    }
+
+    Ok(content)
+}
+
+// Decode input bytes with the named encoding, or as UTF-8 (lossy) by default
+fn decode_input(bytes: &[u8], encoding: Option<&str>) -> Result<String> {
+    let Some(label) = encoding else {
+        return Ok(String::from_utf8_lossy(bytes).into_owned());
+    };
+
+    let Some(encoding) = encoding_rs::Encoding::for_label(label.trim().as_bytes()) else {
+        bail!(
+            "Unknown input encoding '{}'. Use a name like utf-8, gbk, big5, shift_jis or euc-kr",
+            label
+        );
+    };
+
+    let (content, _, had_errors) = encoding.decode(bytes);
+    if had_errors {
+        info!(
+            "Warning: Input has bytes that aren't valid {}, they were replaced",
+            encoding.name()
+        );
+    }
+
+    Ok(content.into_owned())
+}
+
 // Configuration for font selection based on OS
 struct FontConfig {}
 
@@ -130,20 +618,20 @@ impl FontConfig {
 
         match info.os_type() {
             Type::Debian | Type::Ubuntu => {
-                println!("Running on Debian/Ubuntu");
+                info!("Running on Debian/Ubuntu");
                 Self::find_linux_font()
             }
             Type::Linux => {
                 bail!(
-                    "Running on a general Linux distribution. Please provide font location via --font-location"
+                    "Running on a general Linux distribution. Install a CJK font such as Noto Sans CJK (e.g. the noto-fonts-cjk package) and pass it via --font-location"
                 )
             }
             Type::Windows => {
-                println!("Running on Windows");
+                info!("Running on Windows");
                 Ok(Self::get_windows_font())
             }
             Type::Macos => {
-                println!("Running on MacOS");
+                info!("Running on MacOS");
                 Self::find_macos_font()
             }
             _ => {
@@ -155,54 +643,88 @@ impl FontConfig {
         }
     }
 
-    fn find_linux_font() -> Result<String> {
-        const CANDIDATES: &[&str] = &[
+    // Font paths tried in order on each OS; other systems need --font-location
+    fn candidates(os_type: Type) -> Vec<String> {
+        const LINUX: &[&str] = &[
             "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
             "/usr/share/fonts/truetype/droid/DroidSansFallbackFull.ttf",
             "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
         ];
+        const MACOS: &[&str] = &[
+            "/Library/Fonts/Arial Unicode.ttc",
+            "/System/Library/Fonts/STHeiti Medium.ttc",
+            "/System/Library/Fonts/STHeiti Light.ttc",
+        ];
 
-        CANDIDATES
-            .iter()
-            .find(|&&font| Path::new(font).exists())
-            .map(|&font| font.to_string())
-            .context("No suitable CJK font found on Linux system")
+        match os_type {
+            Type::Debian | Type::Ubuntu => LINUX.iter().map(|font| font.to_string()).collect(),
+            Type::Windows => vec![Self::get_windows_font()],
+            Type::Macos => MACOS.iter().map(|font| font.to_string()).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // Log skipped candidates so a fallback font is easy to spot
+    fn find_existing(os_type: Type) -> Option<String> {
+        Self::candidates(os_type).into_iter().find(|font| {
+            let exists = Path::new(font).exists();
+            if !exists {
+                info!("Skipping font candidate (not found): {}", font);
+            }
+            exists
+        })
+    }
+
+    fn find_linux_font() -> Result<String> {
+        Self::find_existing(Type::Debian)
+            .context("No suitable CJK font found on Linux system. Install fonts-noto-cjk or pass --font-location")
     }
 
     fn get_windows_font() -> String {
         let windir = std::env::var("WINDIR").unwrap_or_else(|_| "C:\\Windows".to_string());
-        format!("{}/Fonts/msyh.ttc", windir.replace("\\", "/")).replace(":", "\\:")
+        format!("{}/Fonts/msyh.ttc", windir)
     }
 
     fn find_macos_font() -> Result<String> {
-        const CANDIDATES: &[&str] = &[
-            "/Library/Fonts/Arial Unicode.ttc",
-            "/System/Library/Fonts/STHeiti Medium.ttc",
-            "/System/Library/Fonts/STHeiti Light.ttc",
-        ];
-
-        CANDIDATES
-            .iter()
-            .find(|&&font| Path::new(font).exists())
-            .map(|&font| font.to_string())
-            .context("No suitable CJK font found on MacOS")
+        Self::find_existing(Type::Macos).context("No suitable CJK font found on MacOS")
     }
 }
 
-// Validate and prepare BGM file
-fn validate_bgm(bgm_path: Option<String>) -> Result<Option<String>> {
-    let Some(path) = bgm_path else {
-        println!("No BGM provided");
-        return Ok(None);
-    };
+// Print the default font candidates for this OS and whether each exists
+pub fn list_fonts() {
+    let os_type = os_info::get().os_type();
+    let candidates = FontConfig::candidates(os_type);
 
-    if !Path::new(&path).exists() {
-        println!("BGM file not found at: '{}', processing with no bgm", path);
-        return Ok(None);
+    if candidates.is_empty() {
+        println!(
+            "No built-in font candidates for {:?}. Pass a font via --font-location",
+            os_type
+        );
+        return;
+    }
+
+    println!("Font candidates for {:?}, tried in order:", os_type);
+    for font in candidates {
+        let marker = if Path::new(&font).exists() {
+            "found"
+        } else {
+            "missing"
+        };
+        println!("  [{}] {}", marker, font);
+    }
+}
+
+// Check that a background image exists and FFmpeg can decode it, falling back to the color
+fn validate_background_image(path: PathBuf) -> Option<PathBuf> {
+    if !path.exists() {
+        info!(
+            "Warning: Background image not found at: '{}', using the background color",
+            path.display()
+        );
+        return None;
     }
 
-    // Verify audio stream exists
-    let output = Command::new("ffprobe")
+    let decodable = Command::new("ffprobe")
         .args([
             "-v",
             "error",
@@ -213,261 +735,2959 @@ fn validate_bgm(bgm_path: Option<String>) -> Result<Option<String>> {
         ])
         .arg(&path)
         .output()
-        .context("Failed to run ffprobe. Is it installed?")?;
-
-    if !output.status.success() {
-        println!("Warning: Could not verify BGM audio stream");
-        return Ok(Some(path));
-    }
-
-    let streams = String::from_utf8_lossy(&output.stdout);
-    if !streams.contains("audio") {
-        bail!("BGM file has no audio stream: {}", path);
+        .is_ok_and(|output| {
+            output.status.success() && String::from_utf8_lossy(&output.stdout).contains("video")
+        });
+
+    if !decodable {
+        info!(
+            "Warning: Background image can't be decoded: '{}', using the background color",
+            path.display()
+        );
+        return None;
     }
 
-    println!("BGM found and validated: {}", path);
-    Ok(Some(path))
+    info!("Using background image: {}", path.display());
+    Some(path)
 }
 
-// Build drawtext filter for a single word
-fn build_word_filter(
-    word: &str,
-    font_location: &str,
-    text_color: &str,
-    start_time: f64,
-    end_time: f64,
-) -> String {
-    let escaped_word = word
-        .replace('\\', "\\\\")
-        .replace('\'', "'\\''")
-        .replace(':', "\\:");
-
-    let fontsize = if escaped_word.len() > 50 { 80 } else { 100 };
-
-    format!(
-        "drawtext=fontfile='{}':text='{}':fontcolor={}:fontsize={}:x=(w-text_w)/5*2:y=h/2-ascent:enable='between(t,{},{})'",
-        font_location, escaped_word, text_color, fontsize, start_time, end_time
-    )
+// A BGM input and the stream holding its audio
+#[derive(Debug)]
+struct Bgm {
+    path: String,
+    // Absolute index of the first audio stream; None when ffprobe couldn't tell
+    audio_stream: Option<usize>,
 }
 
-// Build all video filters
-fn build_filters(
-    words: &[String],
-    wpm: u32,
-    text_color: &str,
-    secondary_color: &str,
-    rest_duration: f64,
-    focus_lines: bool,
-    font_location: &str,
-) -> (Vec<String>, f64) {
-    let seconds_per_word = 60.0 / wpm as f64;
-    let mut total_duration = seconds_per_word * (words.len() as f64);
+impl Bgm {
+    // Stream specifier for the audio of input `input`
+    fn source(&self, input: usize) -> String {
+        match self.audio_stream {
+            Some(index) => format!("[{}:{}]", input, index),
+            None => format!("[{}:a:0]", input),
+        }
+    }
+}
 
-    // Use with_capacity when size is known
-    let mut filters = Vec::with_capacity(words.len() + 5);
+// First audio stream in ffprobe "index,codec_type" lines
+fn first_audio_stream(streams: &str) -> Option<usize> {
+    streams.lines().find_map(|line| {
+        let (index, codec_type) = line.trim().split_once(',')?;
+        if codec_type == "audio" {
+            index.parse().ok()
+        } else {
+            None
+        }
+    })
+}
 
-    // Add focus lines
-    if focus_lines {
-        filters.extend([
-            format!(
-                "drawbox=x=0:y=ih*0.2:w=1920:h=10:t=fill:color={}",
-                secondary_color
-            ),
-            format!(
-                "drawbox=x=0:y=ih*0.8:w=1920:h=10:t=fill:color={}",
-                secondary_color
-            ),
-            format!(
-                "drawbox=x=iw*0.4:y=ih*0.2:w=10:h=75:t=fill:color={}",
-                secondary_color
-            ),
-            format!(
-                "drawbox=x=iw*0.4:y=ih*0.8-75:w=10:h=75:t=fill:color={}",
-                secondary_color
-            ),
-        ]);
+// Missing files the user named are an error; missing defaults from the config
+// are skipped with a warning so the video renders without them
+fn validate_bgm(bgm_paths: Vec<String>, from_config: bool) -> Result<Vec<Bgm>> {
+    if bgm_paths.is_empty() {
+        info!("No BGM provided");
+        return Ok(Vec::new());
     }
 
-    // Add word filters with rest periods
-    let mut current_time = 0.0;
-    let mut last_relax_time = 0.0;
+    let missing: Vec<String> = bgm_paths
+        .iter()
+        .filter(|path| !Path::new(path).exists())
+        .cloned()
+        .collect();
+    if !missing.is_empty() && !from_config {
+        return Err(Error::BgmMissing(missing).into());
+    }
 
-    for (i, word) in words.iter().enumerate() {
-        let needs_rest = i > 0
-            && (last_relax_time > current_time + 60.0
-                || word.ends_with('.')
-                || word.ends_with('!')
-                || word.ends_with('?'));
+    let mut valid = Vec::with_capacity(bgm_paths.len());
 
-        let relax_time = if needs_rest {
-            last_relax_time = current_time;
-            total_duration += rest_duration;
-            rest_duration
-        } else {
-            0.0
-        };
+    for path in bgm_paths {
+        if !Path::new(&path).exists() {
+            info!(
+                "Warning: BGM file from the config not found at: '{}', skipping",
+                path
+            );
+            continue;
+        }
 
-        let start_time = current_time;
-        let end_time = current_time + seconds_per_word + relax_time;
+        // Find the audio stream; video files may also carry video and subtitle streams
+        let output = Command::new("ffprobe")
+            .args([
+                "-v",
+                "error",
+                "-show_entries",
+                "stream=index,codec_type",
+                "-of",
+                "csv=p=0",
+            ])
+            .arg(&path)
+            .output()
+            .context("Failed to run ffprobe. Is it installed?")?;
+
+        if !output.status.success() {
+            info!("Warning: Could not verify BGM audio stream: {}", path);
+            valid.push(Bgm {
+                path,
+                audio_stream: None,
+            });
+            continue;
+        }
 
-        filters.push(build_word_filter(
-            word,
-            font_location,
-            text_color,
-            start_time,
-            end_time,
-        ));
+        let streams = String::from_utf8_lossy(&output.stdout);
+        let Some(audio_stream) = first_audio_stream(&streams) else {
+            info!("Warning: BGM file has no audio stream, skipping: {}", path);
+            continue;
+        };
 
-        current_time = end_time;
+        info!("BGM found and validated: {}", path);
+        debug!("Using audio stream {} of {}", audio_stream, path);
+        valid.push(Bgm {
+            path,
+            audio_stream: Some(audio_stream),
+        });
     }
 
-    // Add WPM indicator
-    filters.push(format!(
-        "drawtext=fontfile='{}':text='{} wpm':fontcolor={}:fontsize=60:x=(w-text_w)*0.9:y=(h-text_h)*0.9",
-        font_location, wpm, secondary_color
-    ));
+    if valid.is_empty() {
+        info!("No usable BGM, processing with no bgm");
+    }
 
-    (filters, total_duration)
+    Ok(valid)
 }
 
-// Build FFmpeg command
-fn build_ffmpeg_command(
-    output_file: &str,
-    bg_color: &str,
-    bgm_location: Option<&str>,
-    filter_chain: &str,
-    total_duration: f64,
-    overwrite: bool,
-) -> Command {
-    let mut cmd = Command::new("ffmpeg");
-
-    cmd.env("FONTCONFIG_FILE", "NUL").args([
-        "-hide_banner",
-        "-loglevel",
-        "error",
-        "-hwaccel",
-        "auto",
-        "-f",
-        "lavfi",
-        "-i",
-        &format!("color=c={}:s=1920x1080:d={}:r=30", bg_color, total_duration),
-    ]);
-
-    // Add BGM if present
-    if let Some(bgm) = bgm_location {
-        cmd.args(["-stream_loop", "-1", "-i", bgm]);
-    }
-
-    // Video filter and stream mapping
-    cmd.args(["-vf", filter_chain]);
+// Escape text for drawtext's single-quoted text option, one layer per parser
+fn escape_drawtext(text: &str) -> String {
+    // drawtext expansion: backslash escapes, '%' starts %{...} sequences
+    let expanded = text.replace('\\', "\\\\").replace('%', "\\%");
+    // Option parser: backslash, quote, and ':' separating options
+    let option = expanded
+        .replace('\\', "\\\\")
+        .replace('\'', "\\'")
+        .replace(':', "\\:");
+    // Filtergraph: ',' '[' ']' ';' are safe inside the quotes, but a quote has to
+    // close them, add an escaped quote, and reopen
+    option.replace('\'', "'\\''")
+}
 
-    if bgm_location.is_some() {
-        cmd.args(["-map", "0:v:0", "-map", "1:a:0"]);
+// Font size for a word: fontsize_small above the length threshold, then with
+// --font-size-auto shrunk until the estimated width fits 90% of the frame
+fn word_fontsize(word: &str, args: &crate::Args, frame_width: u32) -> u32 {
+    // Count characters, not bytes, so CJK words aren't shrunk too early;
+    // letter spacing doesn't make a word long
+    let letters = word.chars().filter(|c| *c != THIN_SPACE).count();
+    let fontsize = if letters > args.long_word_threshold {
+        args.fontsize_small
     } else {
-        cmd.args(["-map", "0:v:0"]);
-    }
-
-    // Codec settings
-    cmd.args([
-        "-c:v",
-        "libx264",
-        "-preset",
-        "ultrafast",
-        "-crf",
-        "23",
-        "-pix_fmt",
-        "yuv420p",
-    ]);
+        args.fontsize
+    };
 
-    if bgm_location.is_some() {
-        cmd.args(["-c:a", "aac", "-b:a", "192k", "-shortest"]);
+    if !args.font_size_auto {
+        return fontsize;
     }
 
-    // Overwrite flag
-    if overwrite {
-        cmd.arg("-y");
+    let width_in_tenths = text_width_tenths(word);
+    if width_in_tenths == 0 {
+        return fontsize;
     }
 
-    cmd.arg(output_file);
-    cmd
+    let fitting = usable_width_tenths(args, frame_width) / width_in_tenths;
+    fontsize.min(fitting).max(1)
 }
 
-pub fn generate_video(args: crate::Args) -> Result<()> {
-    let start = Instant::now();
-    // Extract owned values that will be moved
-    let text_opt = args.text;
-    let bgm_opt = args.bgm_location;
-    let font_opt = args.font_location;
-
+// Without font metrics, assume a generous monospace-style advance in tenths
+// of an em: full width for CJK and Hangul, 0.6em for everything else
+fn text_width_tenths(text: &str) -> u32 {
+    text.chars()
+        .map(|c| {
+            let wide = ('\u{1100}'..='\u{11ff}').contains(&c) // Hangul Jamo
+                || ('\u{2e80}'..='\u{9fff}').contains(&c) // CJK, kana
+                || ('\u{ac00}'..='\u{d7af}').contains(&c) // Hangul syllables
+                || ('\u{ff00}'..='\u{ff60}').contains(&c); // Fullwidth forms
+            if wide {
+                10
+            } else if c == THIN_SPACE {
+                2
+            } else {
+                6
+            }
+        })
+        .sum()
+}
+
+// The frame minus the margins (5% each side by default), in tenths of a pixel
+fn usable_width_tenths(args: &crate::Args, frame_width: u32) -> u32 {
+    match args.margin {
+        Some(margin) => frame_width.saturating_sub(margin.saturating_mul(2)) * 10,
+        None => frame_width * 9,
+    }
+}
+
+// Longest a wrapped chunk gets; font_size_auto shrinks whatever still overflows
+const MAX_CHUNK_LINES: usize = 3;
+
+// Soft-wrap a chunk that would overflow the frame at the regular font size into
+// at most MAX_CHUNK_LINES lines of similar width
+fn wrap_chunk(chunk: &str, args: &crate::Args, frame_width: u32) -> Vec<String> {
+    let words: Vec<&str> = chunk
+        .split(' ')
+        .map(|word| word.trim_matches(THIN_SPACE))
+        .collect();
+    let space = text_width_tenths(" ");
+
+    // Greedily fill lines up to the given width, in tenths of an em
+    let fill = |limit: u32| {
+        let mut lines: Vec<String> = Vec::new();
+        let mut width = 0;
+        for word in &words {
+            let word_width = text_width_tenths(word);
+            match lines.last_mut() {
+                Some(line) if width + space + word_width <= limit => {
+                    line.push(' ');
+                    line.push_str(word);
+                    width += space + word_width;
+                }
+                _ => {
+                    lines.push(word.to_string());
+                    width = word_width;
+                }
+            }
+        }
+        lines
+    };
+
+    if words.len() < 2 {
+        return vec![chunk.to_string()];
+    }
+    let budget = usable_width_tenths(args, frame_width) / args.fontsize.max(1);
+    let count = fill(budget).len().min(MAX_CHUNK_LINES);
+    if count < 2 {
+        return vec![chunk.to_string()];
+    }
+
+    // Balance the lines: the narrowest width that still needs no more lines
+    let mut limit = text_width_tenths(chunk).div_ceil(count as u32);
+    loop {
+        let lines = fill(limit);
+        if lines.len() <= count {
+            return lines;
+        }
+        limit += space;
+    }
+}
+
+const THIN_SPACE: char = '\u{2009}';
+
+// drawtext has no letter spacing, so approximate it with thin spaces between
+// graphemes. Right-to-left words are left alone to keep their letters joined
+fn space_letters(text: &str, spaces: u32) -> String {
+    if spaces == 0 || is_rtl(text) {
+        return text.to_string();
+    }
+
+    let gap = THIN_SPACE.to_string().repeat(spaces as usize);
+    text.graphemes(true).collect::<Vec<_>>().join(&gap)
+}
+
+// Default word position, two fifths of the free space left of the word
+const DEFAULT_WORD_X: &str = "(w-text_w)/5*2";
+
+// Text color for a word, or for a chunk when any of its words stands out
+pub(crate) fn word_color<'a>(word: &str, args: &'a crate::Args) -> &'a str {
+    let emphasized = args.auto_emphasize
+        && word
+            .split_whitespace()
+            .any(|part| is_emphasized(part, args.emphasis_min_length, !args.no_emphasis_stoplist));
+    if emphasized {
+        &args.emphasis_color
+    } else {
+        &args.text_color
+    }
+}
+
+// x expression for the word: an explicit --word-x, otherwise the --align preset
+fn word_x(args: &crate::Args) -> &str {
+    args.word_x
+        .as_deref()
+        .unwrap_or_else(|| args.align.x_expression())
+}
+
+// Stack a CJK word top to bottom, one drawtext per glyph, since drawtext only lays
+// out horizontal lines. Baselines are a font size (plus --line-spacing) apart and
+// the column is centered on the middle of the frame
+fn build_vertical_filter(
+    word: &str,
+    args: &crate::Args,
+    font_location: &str,
+    frame_width: u32,
+    start_time: f64,
+    end_time: f64,
+) -> Vec<String> {
+    let glyphs: Vec<&str> = word
+        .graphemes(true)
+        .filter(|glyph| !glyph.trim().is_empty())
+        .collect();
+    let fontsize = glyphs
+        .iter()
+        .map(|glyph| word_fontsize(glyph, args, frame_width))
+        .min()
+        .unwrap_or(args.fontsize);
+    let step = fontsize as f64 + args.line_spacing as f64;
+    let middle = (glyphs.len() as f64 - 1.0) / 2.0;
+    let text_color = word_color(word, args);
+
+    glyphs
+        .iter()
+        .enumerate()
+        .map(|(i, glyph)| {
+            format!(
+                "drawtext=fontfile='{}':text='{}':fontcolor={}:fontsize={}:x='{}':y=h/2{:+}-ascent:enable='between(t,{},{})'",
+                prepare_fontfile(font_location),
+                escape_drawtext(glyph),
+                text_color,
+                fontsize,
+                word_x(args),
+                (i as f64 - middle) * step,
+                start_time,
+                end_time
+            )
+        })
+        .collect()
+}
+
+// Build drawtext filters for a single word (three segments in ORP mode)
+fn build_word_filter(
+    word: &str,
+    args: &crate::Args,
+    font_location: &str,
+    frame_width: u32,
+    start_time: f64,
+    end_time: f64,
+) -> Vec<String> {
+    if args.vertical && has_cjk(word) {
+        return build_vertical_filter(word, args, font_location, frame_width, start_time, end_time);
+    }
+
+    let spaced = space_letters(word, args.letter_spacing);
+    // Pivot alignment and right-to-left shaping need the word on one line
+    let lines = if args.orp || is_rtl(word) {
+        vec![spaced.clone()]
+    } else {
+        wrap_chunk(&spaced, args, frame_width)
+    };
+    let fontsize = lines
+        .iter()
+        .map(|line| word_fontsize(line, args, frame_width))
+        .min()
+        .unwrap_or(args.fontsize);
+    // A wrapped block is centered as a whole; a single line sits on the baseline
+    let y = if lines.len() > 1 {
+        "(h-text_h)/2"
+    } else {
+        "h/2-ascent"
+    };
+    let line_spacing = if args.line_spacing != 0 {
+        format!(":line_spacing={}", args.line_spacing)
+    } else {
+        String::new()
+    };
+
+    let text_color = word_color(word, args);
+
+    let drawtext = |text: &str, color: &str, x: &str| {
+        format!(
+            "drawtext=fontfile='{}':text='{}':fontcolor={}:fontsize={}{}:x='{}':y={}:enable='between(t,{},{})'",
+            prepare_fontfile(font_location),
+            escape_drawtext(text),
+            color,
+            fontsize,
+            line_spacing,
+            x,
+            y,
+            start_time,
+            end_time
+        )
+    };
+
+    // Right-to-left words rely on drawtext's text shaping (FriBidi) for visual order
+    // and are drawn whole, mirroring the default anchor; splitting them at the pivot
+    // would put the parts in logical, left-to-right order
+    if is_rtl(word) {
+        let x = match (&args.word_x, args.align) {
+            (None, Align::Left) => "(w-text_w)/5*3",
+            _ => word_x(args),
+        };
+        return vec![format!("{}:text_shaping=1", drawtext(word, text_color, x))];
+    }
+
+    if !args.orp {
+        return vec![drawtext(&lines.join("\n"), text_color, word_x(args))];
+    }
+
+    // Pin the pivot glyph's left edge to the focus mark; the left part ends there,
+    // keeping the letter spacing before the pivot as trailing thin spaces
+    let (left, pivot, right) = split_at_orp(word);
+    let mut filters = Vec::with_capacity(3);
+
+    if !left.is_empty() {
+        let left = space_letters(left, args.letter_spacing)
+            + &THIN_SPACE.to_string().repeat(args.letter_spacing as usize);
+        filters.push(drawtext(&left, text_color, "w*0.4-text_w"));
+    }
+    filters.push(drawtext(
+        &space_letters(&format!("{}{}", pivot, right), args.letter_spacing),
+        text_color,
+        "w*0.4",
+    ));
+    // Redraw the pivot on top in its own color
+    if let Some(pivot_color) = &args.pivot_color {
+        filters.push(drawtext(pivot, pivot_color, "w*0.4"));
+    }
+
+    filters
+}
+
+// Draw each word's neighbors dimmed, the previous one ending at 20% of the width
+// and the next one starting at 80%, during the word's own time window
+fn build_context_filters(
+    timings: &[(f64, f64, String)],
+    args: &crate::Args,
+    font_location: &str,
+    frame_width: u32,
+) -> Vec<String> {
+    let color = match &args.context_color {
+        Some(color) => color.clone(),
+        None => {
+            let (r, g, b) = color_to_rgb(&args.text_color).unwrap_or((255, 255, 255));
+            format!("#{:02X}{:02X}{:02X}66", r, g, b)
+        }
+    };
+
+    // Countdown digits aren't words, so they neither get nor give context
+    let words = &timings[(args.countdown as usize).min(timings.len())..];
+    let mut filters = Vec::new();
+
+    for (i, (start_time, end_time, _)) in words.iter().enumerate() {
+        let neighbors = [
+            (i.checked_sub(1), "w*0.2-text_w"),
+            (Some(i + 1).filter(|&next| next < words.len()), "w*0.8"),
+        ];
+
+        for (index, x) in neighbors {
+            let Some((_, _, neighbor)) = index.map(|index| &words[index]) else {
+                continue;
+            };
+            filters.push(format!(
+                "drawtext=fontfile='{}':text='{}':fontcolor={}:fontsize={}:x={}:y=h/2-ascent:enable='between(t,{},{})'",
+                prepare_fontfile(font_location),
+                escape_drawtext(neighbor),
+                color,
+                word_fontsize(neighbor, args, frame_width),
+                x,
+                start_time,
+                end_time
+            ));
+        }
+    }
+
+    filters
+}
+
+// Geometry of the focus lines framing the word
+struct FocusLineStyle {
+    // Distance of the horizontal lines from the top/bottom edge, as a fraction of height
+    offset: f64,
+    // Line thickness in pixels
+    thickness: u32,
+    // Length of the vertical ticks, as a fraction of height (75px at 1080p)
+    tick_height: f64,
+    // Horizontal position of the ticks, as a fraction of width
+    tick_x: f64,
+}
+
+impl FocusLineStyle {
+    fn from_args(args: &crate::Args) -> Self {
+        Self {
+            offset: args.focus_offset,
+            thickness: args.focus_thickness,
+            tick_height: 75.0 / 1080.0,
+            tick_x: 0.4,
+        }
+    }
+
+    fn build_filters(&self, resolution: &Resolution, color: &str) -> Vec<String> {
+        let tick_height = (resolution.height as f64 * self.tick_height).round() as u32;
+        let top = self.offset;
+        let bottom = 1.0 - self.offset;
+
+        vec![
+            format!(
+                "drawbox=x=0:y=ih*{}:w={}:h={}:t=fill:color={}",
+                top, resolution.width, self.thickness, color
+            ),
+            format!(
+                "drawbox=x=0:y=ih*{}:w={}:h={}:t=fill:color={}",
+                bottom, resolution.width, self.thickness, color
+            ),
+            format!(
+                "drawbox=x=iw*{}:y=ih*{}:w={}:h={}:t=fill:color={}",
+                self.tick_x, top, self.thickness, tick_height, color
+            ),
+            format!(
+                "drawbox=x=iw*{}:y=ih*{}-{}:w={}:h={}:t=fill:color={}",
+                self.tick_x, bottom, tick_height, self.thickness, tick_height, color
+            ),
+        ]
+    }
+}
+
+// Compute (start, end, word) for each word, including rest periods
+fn compute_timings(words: &[Token], args: &crate::Args) -> (Vec<(f64, f64, String)>, f64) {
+    let seconds_per_word = seconds_per_word(args);
+    let chunks = chunk_words(words, args.chunk_size);
+    let mut timings = Vec::with_capacity(chunks.len());
+
+    let mut current_time = 0.0;
+    let mut last_relax_time = 0.0;
+
+    for (i, (chunk, word_count, last_word)) in chunks.into_iter().enumerate() {
+        // Punctuation scales the rest; a periodic rest is at least a full one
+        let weight = if current_time - last_relax_time >= args.periodic_rest_interval {
+            pause_weight(last_word, args).max(1.0)
+        } else {
+            pause_weight(last_word, args)
+        };
+
+        let relax_time = if i > 0 && weight > 0.0 {
+            last_relax_time = current_time;
+            args.rest_duration * weight
+        } else {
+            0.0
+        };
+
+        // Chunks stay on screen once per word so the effective WPM is unchanged
+        let start_time = current_time;
+        let display_end = current_time + seconds_per_word * word_count as f64;
+        // The gap is taken out of the display time, leaving at least one frame
+        let gap = args
+            .word_gap
+            .min(display_end - start_time - 1.0 / args.fps as f64)
+            .max(0.0);
+
+        // Paragraph breaks clear the screen for the rest as a visual reset;
+        // other rests keep the word up
+        let end_time = if last_word.ends_paragraph {
+            display_end - gap
+        } else {
+            display_end + relax_time - gap
+        };
+
+        timings.push((start_time, end_time, chunk));
+
+        current_time = display_end + relax_time;
+    }
+
+    (timings, current_time)
+}
+
+// Play the words backward, keeping each pause in the same gap between words
+fn reverse_words(words: Vec<Token>) -> Vec<Token> {
+    let pauses: Vec<_> = words
+        .iter()
+        .map(|word| (word.ends_sentence, word.ends_clause, word.ends_paragraph))
+        .collect();
+    let count = words.len();
+
+    words
+        .into_iter()
+        .rev()
+        .enumerate()
+        .map(|(i, word)| {
+            // The gap after reversed word i was marked on original word count-2-i
+            let (ends_sentence, ends_clause, ends_paragraph) = (count - i)
+                .checked_sub(2)
+                .map_or((false, false, false), |original| pauses[original]);
+            Token {
+                ends_sentence,
+                ends_clause,
+                ends_paragraph,
+                ..word
+            }
+        })
+        .collect()
+}
+
+// Play the words `times` times back-to-back, with a paragraph pause between passes
+fn repeat_words(words: Vec<Token>, times: u32) -> Vec<Token> {
+    let mut repeated: Vec<Token> = Vec::with_capacity(words.len() * times as usize);
+
+    for pass in 0..times {
+        if pass > 0
+            && let Some(last) = repeated.last_mut()
+        {
+            last.ends_paragraph = true;
+        }
+        repeated.extend(words.iter().cloned());
+    }
+
+    repeated
+}
+
+// Keep words[start..end]; timings are computed on the slice so it starts at 0s
+fn slice_words<T>(mut words: Vec<T>, start: Option<usize>, end: Option<usize>) -> Result<Vec<T>> {
+    let len = words.len();
+    let start = start.unwrap_or(0);
+    let end = end.unwrap_or(len);
+
+    if start >= len {
+        bail!(
+            "Invalid start word {}. The text only has {} words",
+            start,
+            len
+        );
+    }
+    if start >= end {
+        bail!(
+            "Invalid word range {}..{}. The start word must be before the end word",
+            start,
+            end
+        );
+    }
+    if end > len {
+        bail!("Invalid end word {}. The text only has {} words", end, len);
+    }
+
+    words.truncate(end);
+    words.drain(..start);
+    Ok(words)
+}
+
+// Keep the timings that start before `seconds`, ending the last one there
+fn truncate_timings(mut timings: Vec<(f64, f64, String)>, seconds: f64) -> Vec<(f64, f64, String)> {
+    timings.retain(|(start, _, _)| *start < seconds);
+    if let Some((_, end, _)) = timings.last_mut() {
+        *end = end.min(seconds);
+    }
+    timings
+}
+
+// Prepend one-second "N", ..., "1" frames and shift the word timings after them
+fn add_countdown(timings: Vec<(f64, f64, String)>, seconds: u32) -> Vec<(f64, f64, String)> {
+    let offset = seconds as f64;
+    let countdown = (0..seconds).map(|i| (i as f64, (i + 1) as f64, (seconds - i).to_string()));
+    let words = timings
+        .into_iter()
+        .map(|(start, end, word)| (start + offset, end + offset, word));
+
+    countdown.chain(words).collect()
+}
+
+// Display time of one word: --spw if set, otherwise derived from --wpm
+fn seconds_per_word(args: &crate::Args) -> f64 {
+    args.spw.unwrap_or(60.0 / args.wpm as f64)
+}
+
+// WPM shown to the viewer, the equivalent of --spw when it is set
+fn effective_wpm(args: &crate::Args) -> u32 {
+    args.spw.map_or(args.wpm, |spw| (60.0 / spw).round() as u32)
+}
+
+// Multiple of --rest-duration to pause after a word
+fn pause_weight(word: &Token, args: &crate::Args) -> f64 {
+    if word.ends_paragraph {
+        args.paragraph_pause
+    } else if word.ends_sentence {
+        args.sentence_pause
+    } else if word.ends_clause {
+        args.comma_pause
+    } else {
+        0.0
+    }
+}
+
+// Group words into (text, word count, last word) chunks of up to chunk_size words.
+// A chunk ends early at a sentence or paragraph end so pauses fall on chunk boundaries.
+fn chunk_words(words: &[Token], chunk_size: usize) -> Vec<(String, usize, &Token)> {
+    let mut chunks = Vec::with_capacity(words.len() / chunk_size.max(1) + 1);
+    let mut current: Vec<&str> = Vec::with_capacity(chunk_size);
+
+    for word in words {
+        current.push(&word.text);
+
+        if word.ends_sentence || word.ends_paragraph || current.len() >= chunk_size {
+            chunks.push((current.join(" "), current.len(), word));
+            current.clear();
+        }
+    }
+
+    if let Some(last) = words.last()
+        && !current.is_empty()
+    {
+        chunks.push((current.join(" "), current.len(), last));
+    }
+
+    chunks
+}
+
+// Build all video filters
+fn build_filters(
+    timings: &[(f64, f64, String)],
+    args: &crate::Args,
+    font_location: &str,
+    resolution: &Resolution,
+    subtitle_path: Option<&Path>,
+) -> Vec<String> {
+    let secondary_color = args.secondary_color.as_str();
+
+    // Use with_capacity when size is known
+    let mut filters = Vec::with_capacity(timings.len() + 5);
+
+    // Blur and shade the background before any text is drawn so the words stay crisp
+    if let Some(sigma) = args.blur_background {
+        filters.push(format!("gblur=sigma={}", sigma));
+    }
+    if args.vignette {
+        filters.push("vignette".to_string());
+    }
+
+    // Add focus lines
+    if args.focus_lines {
+        let style = FocusLineStyle::from_args(args);
+        let focus_color = args.focus_color.as_deref().unwrap_or(secondary_color);
+        filters.extend(style.build_filters(resolution, focus_color));
+    }
+
+    // Add words, either as one subtitles filter or one drawtext per word
+    if let Some(path) = subtitle_path {
+        let fonts_dir = Path::new(font_location)
+            .parent()
+            .map(|dir| dir.to_string_lossy().to_string())
+            .unwrap_or_default();
+        filters.push(format!(
+            "subtitles=filename='{}':fontsdir='{}'",
+            escape_filter_path(&path.to_string_lossy()),
+            prepare_fontfile(&fonts_dir)
+        ));
+    } else {
+        for (start_time, end_time, word) in timings {
+            filters.extend(build_word_filter(
+                word,
+                args,
+                font_location,
+                resolution.width,
+                *start_time,
+                *end_time,
+            ));
+        }
+
+        if args.context_words {
+            filters.extend(build_context_filters(
+                timings,
+                args,
+                font_location,
+                resolution.width,
+            ));
+        }
+    }
+
+    // Progress bar that reaches full width as the last word ends. drawbox sizes
+    // are fixed at setup (its `t` is the thickness), so a full-width bar cut from
+    // the frame slides in through overlay, whose x is evaluated on every frame
+    if args.show_progress_overlay
+        && let Some((_, end_time, _)) = timings.last()
+    {
+        let height = (resolution.height / 180).max(2);
+        filters.push(format!(
+            "split[progress_main][progress_in];[progress_in]crop=iw:{h}:0:ih-{h},drawbox=x=0:y=0:w=iw:h=ih:t=fill:color={color}[progress_bar];[progress_main][progress_bar]overlay=x='-w+w*min(1,t/{end})':y=H-h",
+            h = height,
+            color = secondary_color,
+            end = end_time
+        ));
+    }
+
+    // Hold the outro text centered after the last word
+    if let Some(text) = &args.outro_text {
+        let start_time = timings.last().map_or(0.0, |(_, end, _)| *end);
+        filters.push(format!(
+            "drawtext=fontfile='{}':text='{}':fontcolor={}:fontsize={}:x=(w-text_w)/2:y=(h-text_h)/2:enable='between(t,{},{})'",
+            prepare_fontfile(font_location),
+            escape_drawtext(text),
+            args.text_color,
+            word_fontsize(text, args, resolution.width),
+            start_time,
+            start_time + args.outro_duration
+        ));
+    }
+
+    // Add the watermark (WPM indicator by default)
+    let label = match args.watermark {
+        Watermark::None => None,
+        Watermark::Wpm => Some(format!("{} wpm", effective_wpm(args))),
+        Watermark::Custom => args.watermark_text.clone(),
+    };
+    if let Some(label) = label {
+        let (x, y) = args.watermark_position.coordinates();
+        filters.push(format!(
+            "drawtext=fontfile='{}':text='{}':fontcolor={}:fontsize=60:x={}:y={}",
+            prepare_fontfile(font_location),
+            escape_drawtext(&label),
+            secondary_color,
+            x,
+            y
+        ));
+    }
+
+    filters
+}
+
+// Render one tiny frame with the font so a bad file fails fast with FFmpeg's reason
+fn check_font(font_location: &str) -> Result<()> {
+    if !Path::new(font_location).is_file() {
+        return Err(Error::FontNotFound(font_location.to_string()).into());
+    }
+
+    let output = Command::new("ffmpeg")
+        .args([
+            "-v",
+            "error",
+            "-f",
+            "lavfi",
+            "-i",
+            "color=c=black:s=64x64:d=0.1",
+            "-vf",
+            &format!(
+                "drawtext=fontfile='{}':text=x",
+                prepare_fontfile(font_location)
+            ),
+            "-frames:v",
+            "1",
+            "-f",
+            "null",
+            "-",
+        ])
+        .output()
+        .context("Failed to execute ffmpeg. Is it installed?")?;
+
+    if !output.status.success() {
+        bail!(
+            "FFmpeg can't load the font {}:\n{}",
+            font_location,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(())
+}
+
+// Escape a font path for drawtext's single-quoted fontfile option. Windows paths
+// get forward slashes, leaving the drive colon as the only separator to escape.
+fn prepare_fontfile(path: &str) -> String {
+    let path = if cfg!(windows) {
+        path.replace('\\', "/")
+    } else {
+        path.to_string()
+    };
+
+    // Same option parser and filtergraph layers as escape_drawtext
+    path.replace('\\', "\\\\")
+        .replace('\'', "\\'")
+        .replace(':', "\\:")
+        .replace('\'', "'\\''")
+}
+
+// Escape a file path for use as a filter option value
+fn escape_filter_path(path: &str) -> String {
+    path.replace('\\', "/")
+        .replace('\'', "'\\''")
+        .replace(':', "\\:")
+}
+
+// Build BGM audio filters
+fn build_audio_filters(args: &crate::Args, total_duration: f64) -> Vec<String> {
+    let mut filters = Vec::new();
+
+    if let Some(volume) = args.bgm_volume {
+        filters.push(format!("volume={}", volume));
+    }
+
+    if let Some(fade_in) = args.bgm_fade_in {
+        filters.push(format!("afade=t=in:st=0:d={}", fade_in));
+    }
+
+    // Fade out relative to the final video length (including rest periods)
+    if let Some(fade_out) = args.bgm_fade_out {
+        let fade_start = (total_duration - fade_out).max(0.0);
+        filters.push(format!("afade=t=out:st={}:d={}", fade_start, fade_out));
+    }
+
+    filters
+}
+
+// Rough render-time estimate from frame count, frame size and filter count.
+// The constants are ballpark figures, not measurements of this machine.
+fn estimate_render_seconds(
+    total_duration: f64,
+    args: &crate::Args,
+    resolution: &Resolution,
+    filter_count: usize,
+) -> f64 {
+    let frames = total_duration * args.fps as f64;
+    let megapixels = resolution.width as f64 * resolution.height as f64 / 1_000_000.0;
+
+    // Encode cost per frame and megapixel, in seconds
+    let is_hardware = HW_ENCODERS.contains(&args.vcodec.as_str());
+    let encode_cost = match args.vcodec.as_str() {
+        "libx264" => match args.preset.as_deref().unwrap_or("ultrafast") {
+            "ultrafast" | "superfast" => 0.0015,
+            "veryfast" | "faster" | "fast" => 0.003,
+            "medium" => 0.005,
+            _ => 0.012,
+        },
+        _ if is_hardware => 0.0005,
+        _ => 0.006,
+    };
+
+    // Every drawtext filter's enable expression is evaluated on every frame
+    let filter_cost = 0.00002 * filter_count as f64;
+
+    frames * (encode_cost * megapixels + filter_cost)
+}
+
+// Build the audio filter graph for BGM inputs (1..=bgm.len()) and narration,
+// returning the graph and the output label to map
+fn build_audio_graph(
+    args: &crate::Args,
+    bgm: &[Bgm],
+    narration: Option<&tts::Narration>,
+    total_duration: f64,
+) -> Option<(String, String)> {
+    let mut graph = Vec::new();
+    let mut outputs = Vec::new();
+
+    let bgm_count = bgm.len();
+    if bgm_count > 0 {
+        let sources: String = bgm
+            .iter()
+            .enumerate()
+            .map(|(i, track)| track.source(i + 1))
+            .collect();
+        let mut chain = Vec::new();
+        if bgm_count > 1 {
+            chain.push(format!("concat=n={}:v=0:a=1", bgm_count));
+            chain.push(format!("aloop=loop={}:size=2147483647", args.bgm_loop));
+        }
+        // Finite BGM is padded with silence so -shortest doesn't cut the video
+        if args.bgm_loop >= 0 {
+            chain.push("apad".to_string());
+        }
+        chain.extend(build_audio_filters(args, total_duration));
+        if chain.is_empty() {
+            chain.push("anull".to_string());
+        }
+
+        graph.push(format!("{}{}[bgm]", sources, chain.join(",")));
+        outputs.push("[bgm]");
+    }
+
+    if let Some(narration) = narration {
+        graph.push(format!(
+            "[{}:a:0]{}[tts]",
+            bgm_count + 1,
+            narration.audio_filters().join(",")
+        ));
+        outputs.push("[tts]");
+    }
+
+    // Mix the BGM under the narration when both are present
+    let label = match outputs[..] {
+        [] => return None,
+        [single] => single.to_string(),
+        _ => {
+            graph.push(format!(
+                "{}amix=inputs={}:duration=longest[aout]",
+                outputs.concat(),
+                outputs.len()
+            ));
+            "[aout]".to_string()
+        }
+    };
+
+    Some((graph.join(";"), label))
+}
+
+// Add the BGM and narration inputs after the video input
+fn add_audio_inputs(
+    cmd: &mut Command,
+    args: &crate::Args,
+    bgm_locations: &[Bgm],
+    narration: Option<&tts::Narration>,
+) {
+    // A single file loops at the input, a playlist loops after concat
+    if let [bgm] = bgm_locations {
+        cmd.args(["-stream_loop", &args.bgm_loop.to_string(), "-i", &bgm.path]);
+    } else {
+        for bgm in bgm_locations {
+            cmd.args(["-i", &bgm.path]);
+        }
+    }
+
+    // Narration comes after the BGM
+    if let Some(narration) = narration {
+        cmd.arg("-i").arg(&narration.path);
+    }
+}
+
+// Mix the audio inputs and encode them, if there are any
+fn add_audio_output(
+    cmd: &mut Command,
+    args: &crate::Args,
+    bgm_locations: &[Bgm],
+    narration: Option<&tts::Narration>,
+    total_duration: f64,
+) {
+    if let Some((graph, label)) = build_audio_graph(args, bgm_locations, narration, total_duration)
+    {
+        cmd.args(["-filter_complex", &graph, "-map", &label]);
+        cmd.args(["-c:a", &args.acodec, "-b:a", &args.abitrate, "-shortest"]);
+    }
+}
+
+// Build FFmpeg command
+fn build_ffmpeg_command(
+    args: &crate::Args,
+    bgm_locations: &[Bgm],
+    narration: Option<&tts::Narration>,
+    filter_chain: &str,
+    total_duration: f64,
+    resolution: &Resolution,
+    container: Container,
+) -> Command {
+    let mut cmd = Command::new("ffmpeg");
+
+    let fps = if container == Container::Gif {
+        args.fps.min(GIF_MAX_FPS)
+    } else {
+        args.fps
+    };
+
+    cmd.env("FONTCONFIG_FILE", "NUL").args([
+        "-hide_banner",
+        "-loglevel",
+        "error",
+        "-progress",
+        "pipe:1",
+        "-nostats",
+        "-hwaccel",
+        &args.hwaccel,
+    ]);
+
+    // The first input is either a looped still image or a solid color
+    let video_chain = if let Some(image) = &args.background_image {
+        cmd.args([
+            "-loop",
+            "1",
+            "-framerate",
+            &fps.to_string(),
+            "-t",
+            &total_duration.to_string(),
+        ]);
+        cmd.arg("-i").arg(image);
+
+        // Cover the frame: scale up to fill it, then crop the overflow
+        format!(
+            "scale={w}:{h}:force_original_aspect_ratio=increase,crop={w}:{h},setsar=1,{chain}",
+            w = resolution.width,
+            h = resolution.height,
+            chain = filter_chain
+        )
+    } else {
+        // A vertical two-color gradient; gradients rotates its points over time,
+        // so the slowest speed keeps it still for any realistic length
+        let source = match &args.bg_gradient {
+            Some(bottom) => format!(
+                "gradients=s={}:d={}:r={}:c0={}:c1={}:nb_colors=2:x0=0:y0=0:x1=0:y1={}:speed=0.00001",
+                resolution, total_duration, fps, args.bg_color, bottom, resolution.height
+            ),
+            None => format!(
+                "color=c={}:s={}:d={}:r={}",
+                args.bg_color, resolution, total_duration, fps
+            ),
+        };
+        cmd.args(["-f", "lavfi", "-i", &source]);
+        filter_chain.to_string()
+    };
+
+    add_audio_inputs(&mut cmd, args, bgm_locations, narration);
+
+    // GIFs have no audio and build a palette from the rendered frames in the same graph
+    if container == Container::Gif {
+        let gif_chain = format!(
+            "{},split[frames][palette_in];[palette_in]palettegen[palette];[frames][palette]paletteuse",
+            video_chain
+        );
+        cmd.args(["-vf", &gif_chain, "-map", "0:v:0"]);
+    } else {
+        // Video filter and stream mapping
+        cmd.args(["-vf", &video_chain, "-map", "0:v:0"]);
+
+        add_audio_output(&mut cmd, args, bgm_locations, narration, total_duration);
+
+        // Codec settings; x264 gets fast defaults, other encoders only get what the user asked for
+        cmd.args(["-c:v", &args.vcodec]);
+
+        let is_x264 = args.vcodec == "libx264";
+        if let Some(preset) = args.preset.as_deref().or(is_x264.then_some("ultrafast")) {
+            cmd.args(["-preset", preset]);
+        }
+        if let Some(crf) = args.crf.or(is_x264.then_some(23)) {
+            cmd.args(["-crf", &crf.to_string()]);
+        }
+
+        cmd.args(["-pix_fmt", &args.pix_fmt]);
+    }
+
+    // FFmpeg writes a temporary sibling that is renamed into place on success.
+    // It is ours to replace, so -y also keeps FFmpeg from prompting on stdin,
+    // which may be the already-consumed text pipe
+    cmd.args(["-y", "-f", container.muxer()]);
+    cmd.arg(partial_path(&args.output));
+    cmd
+}
+
+// Temporary output next to the destination, so an interrupted encode never
+// leaves a corrupt file under the real name
+fn partial_path(output: &str) -> String {
+    format!("{}.partial", output)
+}
+
+// Run FFmpeg, printing progress parsed from `-progress pipe:1` while capturing stderr
+fn run_with_progress(cmd: &mut Command, total_duration: f64) -> Result<Output> {
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stdout = child
+        .stdout
+        .take()
+        .context("Failed to capture ffmpeg stdout")?;
+
+    let reader = std::thread::spawn(move || {
+        let mut last_percent = None;
+        for line in BufReader::new(stdout).lines().map_while(|line| line.ok()) {
+            // out_time_ms is reported in microseconds despite its name
+            if let Some(value) = line.strip_prefix("out_time_ms=")
+                && let Ok(micros) = value.trim().parse::<f64>()
+                && total_duration > 0.0
+            {
+                let percent =
+                    (micros / 1_000_000.0 / total_duration * 100.0).clamp(0.0, 100.0) as u32;
+                if last_percent != Some(percent) && log::enabled(log::Level::Normal) {
+                    print!("\rRendering video... {}%", percent);
+                    let _ = io::stdout().flush();
+                    last_percent = Some(percent);
+                }
+            } else if line == "progress=end" {
+                info!("\rRendering video... 100%");
+            }
+        }
+    });
+
+    let mut stderr = Vec::new();
+    if let Some(mut pipe) = child.stderr.take() {
+        pipe.read_to_end(&mut stderr)
+            .context("Failed to read ffmpeg stderr")?;
+    }
+
+    let status = child.wait()?;
+    let _ = reader.join();
+
+    Ok(Output {
+        status,
+        stdout: Vec::new(),
+        stderr,
+    })
+}
+
+// Quote a single argument for POSIX shells
+fn shell_quote(arg: &str) -> String {
+    let is_safe = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+
+    if is_safe {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+// Render a command as a copy-pasteable shell line, including env overrides
+fn format_command(cmd: &Command) -> String {
+    let envs = cmd.get_envs().filter_map(|(key, value)| {
+        value.map(|value| {
+            format!(
+                "{}={}",
+                key.to_string_lossy(),
+                shell_quote(&value.to_string_lossy())
+            )
+        })
+    });
+    let program = std::iter::once(shell_quote(&cmd.get_program().to_string_lossy()));
+    let args = cmd
+        .get_args()
+        .map(|arg| shell_quote(&arg.to_string_lossy()));
+
+    envs.chain(program)
+        .chain(args)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Check option values that don't depend on the input text, returning the
+// output resolution. Shared by rendering and the check subcommand
+fn validate_args(args: &crate::Args) -> Result<Resolution> {
+    // Validate colors
+    validate_color(&args.text_color).context("Invalid text color")?;
+    validate_color(&args.bg_color).context("Invalid background color")?;
+    validate_color(&args.secondary_color).context("Invalid secondary color")?;
+
+    if let Some(bg_gradient) = &args.bg_gradient {
+        validate_color(bg_gradient).context("Invalid background gradient color")?;
+    }
+
+    if let Some(focus_color) = &args.focus_color {
+        validate_color(focus_color).context("Invalid focus color")?;
+    }
+
+    if args.auto_emphasize {
+        validate_color(&args.emphasis_color).context("Invalid emphasis color")?;
+    }
+
+    if let Some(pivot_color) = &args.pivot_color {
+        validate_color(pivot_color).context("Invalid pivot color")?;
+    }
+
+    if let Some(context_color) = &args.context_color {
+        validate_color(context_color).context("Invalid context color")?;
+    }
+
+    // Parse output resolution
+    let resolution = args
+        .resolution
+        .as_deref()
+        .map(parse_resolution)
+        .transpose()
+        .context("Invalid resolution")?
+        .unwrap_or_default();
+
+    if let Some(word_x) = &args.word_x {
+        validate_x_expression(word_x).context("Invalid --word-x")?;
+    }
+    if args.orp && args.vertical {
+        info!("Warning: --orp is ignored for CJK words drawn with --vertical");
+    }
+    if args.orp && (args.word_x.is_some() || args.align != Align::Left) {
+        info!(
+            "Warning: --word-x and --align are ignored with --orp, which aligns words on the pivot letter"
+        );
+    }
+    if args
+        .margin
+        .is_some_and(|margin| margin.saturating_mul(2) >= resolution.width)
+    {
+        bail!(
+            "Invalid margin. Both margins together must be narrower than the {}px frame",
+            resolution.width
+        );
+    }
+
+    // Validate BGM volume (linear gain)
+    if let Some(volume) = args.bgm_volume
+        && !(0.0..=1.0).contains(&volume)
+    {
+        bail!(
+            "Invalid BGM volume {}. Use a linear gain between 0.0 and 1.0",
+            volume
+        );
+    }
+
+    // Validate BGM fades
+    for (name, fade) in [
+        ("fade-in", args.bgm_fade_in),
+        ("fade-out", args.bgm_fade_out),
+    ] {
+        if let Some(duration) = fade
+            && duration < 0.0
+        {
+            bail!(
+                "Invalid BGM {} {}. Use a non-negative duration",
+                name,
+                duration
+            );
+        }
+    }
+
+    if args.bgm_loop < -1 {
+        bail!(
+            "Invalid BGM loop count {}. Use -1 to loop forever or 0 and up for extra plays",
+            args.bgm_loop
+        );
+    }
+
+    if args.watermark == Watermark::Custom && args.watermark_text.is_none() {
+        bail!("--watermark custom needs --watermark-text");
+    }
+
+    if args.outro_text.is_some() && args.outro_duration <= 0.0 {
+        bail!(
+            "Invalid outro duration {}. Use a positive number of seconds",
+            args.outro_duration
+        );
+    }
+
+    if args.chunk_size == 0 {
+        bail!("Invalid chunk size 0. Use at least 1 word per frame");
+    }
+
+    for (name, weight) in [
+        ("comma", args.comma_pause),
+        ("sentence", args.sentence_pause),
+        ("paragraph", args.paragraph_pause),
+    ] {
+        if weight < 0.0 {
+            bail!(
+                "Invalid {} pause {}. Use a non-negative multiple of --rest-duration",
+                name,
+                weight
+            );
+        }
+    }
+
+    if args.periodic_rest_interval <= 0.0 {
+        bail!(
+            "Invalid periodic rest interval {}. Use a positive number of seconds",
+            args.periodic_rest_interval
+        );
+    }
+
+    // Validate focus line geometry
+    if !(0.0..0.5).contains(&args.focus_offset) {
+        bail!(
+            "Invalid focus offset {}. Use a fraction of the frame height from 0.0 to below 0.5",
+            args.focus_offset
+        );
+    }
+    if args.focus_thickness == 0 {
+        bail!("Invalid focus thickness 0. Use at least 1 pixel");
+    }
+
+    // Validate frame rate; word timings are in seconds so they don't depend on it
+    if !(1..=240).contains(&args.fps) {
+        bail!("Invalid fps {}. Use a value between 1 and 240", args.fps);
+    }
+
+    if args
+        .blur_background
+        .is_some_and(|sigma| sigma <= 0.0 || !sigma.is_finite())
+    {
+        bail!("Invalid --blur-background. Use a positive blur sigma, e.g. 8");
+    }
+
+    if args.word_gap < 0.0 {
+        bail!(
+            "Invalid word gap {}. Use a non-negative number of seconds",
+            args.word_gap
+        );
+    }
+    if args.word_gap >= seconds_per_word(args) {
+        info!(
+            "Warning: --word-gap {} is not shorter than a word's {:.3}s, words will only flash for one frame",
+            args.word_gap,
+            seconds_per_word(args)
+        );
+    }
+
+    if args.preview.is_some_and(|preview| preview <= 0.0) {
+        bail!("Invalid preview length. Use a positive number of seconds");
+    }
+
+    if args.jobs == 0 {
+        bail!("Invalid --jobs 0. Use 1 or more FFmpeg processes");
+    }
+
+    if args.max_word_length == 0 {
+        bail!("Invalid max word length 0. Use 1 or more characters");
+    }
+
+    if args.repeat == 0 {
+        bail!("Invalid repeat count 0. Use 1 or more passes");
+    }
+
+    if let Some(spw) = args.spw
+        && spw <= 0.0
+    {
+        bail!(
+            "Invalid seconds per word {}. Use a positive number of seconds",
+            spw
+        );
+    }
+
+    if args
+        .max_duration
+        .is_some_and(|max_duration| max_duration <= 0.0)
+    {
+        bail!("Invalid max duration. Use a positive number of seconds");
+    }
+
+    validate_bitrate(&args.abitrate)?;
+
+    if args.pix_fmt.is_empty() || !args.pix_fmt.chars().all(|c| c.is_ascii_alphanumeric()) {
+        bail!(
+            "Invalid pixel format '{}'. Use e.g. yuv420p, yuv444p or yuv420p10le",
+            args.pix_fmt
+        );
+    }
+    if args.pix_fmt != "yuv420p" {
+        info!(
+            "Warning: pixel format {} may not play in browsers and on many phones and TVs; yuv420p plays everywhere",
+            args.pix_fmt
+        );
+    }
+
+    Ok(resolution)
+}
+
+// Accept FFmpeg bitrates as plain bits per second or with a k/M suffix
+fn validate_bitrate(bitrate: &str) -> Result<()> {
+    let digits = bitrate.strip_suffix(['k', 'K', 'M']).unwrap_or(bitrate);
+
+    if !digits.parse::<u32>().is_ok_and(|value| value > 0) {
+        bail!(
+            "Invalid audio bitrate '{}'. Use e.g. 128k or 96000",
+            bitrate
+        );
+    }
+
+    Ok(())
+}
+
+// Read the input text and split it, keeping only the requested slice
+fn read_words(text: Option<String>, args: &crate::Args) -> Result<Vec<Token>> {
+    // Get input text from argument, file, EPUB chapter, or stdin (in that order)
+    let text = match (text, args.input_file.as_deref(), args.epub.as_deref()) {
+        (Some(text), _, _) => text,
+        (None, Some(path), _) => get_file_input(path, args.input_encoding.as_deref())?,
+        (None, None, Some(path)) => epub::read_chapter(path, args.chapter)?,
+        (None, None, None) => get_piped_input(args.input_encoding.as_deref())?,
+    };
+    let text = if args.strip_markdown {
+        strip_markdown(&text)
+    } else {
+        text
+    };
+    if text.trim().is_empty() {
+        return Err(Error::EmptyInput("text".to_string()).into());
+    }
+
+    // The Jieba dictionary is large, so only load it when there is CJK text to cut
+    let jieba = if args.no_cjk || !has_cjk(&text) {
+        None
+    } else {
+        Some(load_jieba(args.jieba_dict.as_deref())?)
+    };
+
+    // A run without spaces (a pasted blob, unsegmented text) would overflow the frame
+    let words = split_long_words(
+        split_text(&text, jieba.as_ref(), &args.abbreviations),
+        args.max_word_length,
+    );
+
+    slice_words(words, args.start_word, args.end_word)
+}
+
+// Words and timings as they will be rendered, shared by check and generate_video
+struct Timeline {
+    words: Vec<Token>,
+    timings: Vec<(f64, f64, String)>,
+    words_duration: f64,
+    total_duration: f64,
+}
+
+// Read the words, reorder and repeat them, and time them after the countdown
+fn build_timeline(text: Option<String>, args: &crate::Args) -> Result<Timeline> {
+    let mut words = read_words(text, args)?;
+    if args.reverse {
+        words = reverse_words(words);
+    }
+    let words = repeat_words(words, args.repeat);
+
+    let (timings, words_duration) = compute_timings(&words, args);
+    let timings = add_countdown(timings, args.countdown);
+    let outro = if args.outro_text.is_some() {
+        args.outro_duration
+    } else {
+        0.0
+    };
+    let total_duration = words_duration + args.countdown as f64 + outro;
+
+    Ok(Timeline {
+        words,
+        timings,
+        words_duration,
+        total_duration,
+    })
+}
+
+// Cut the timeline to --preview and refuse a video over --max-duration
+fn limit_timeline(timeline: &mut Timeline, args: &crate::Args) -> Result<()> {
+    // A preview keeps the words that start in time
+    if let Some(preview) = args.preview {
+        timeline.timings = truncate_timings(std::mem::take(&mut timeline.timings), preview);
+        timeline.total_duration = timeline.total_duration.min(preview);
+    }
+
+    // A huge input at a low WPM could fill the disk before anyone notices
+    if let Some(max_duration) = args.max_duration
+        && timeline.total_duration > max_duration
+    {
+        bail!(
+            "The video would be {:.0}s long, over --max-duration {}s. Render a part with --start-word/--end-word or raise --wpm",
+            timeline.total_duration,
+            max_duration
+        );
+    }
+
+    Ok(())
+}
+
+// Validate the options, input and font without rendering
+pub fn check(mut args: crate::Args) -> Result<()> {
+    let resolution = validate_args(&args)?;
+    Container::resolve(args.format, &args.output)?;
+
+    let mut timeline = build_timeline(args.text.take(), &args)?;
+    limit_timeline(&mut timeline, &args)?;
+
+    let font_location = match args.font_location.take() {
+        Some(font) => font,
+        None => FontConfig::get_default_font().context("No font available")?,
+    };
+    check_font(&font_location)?;
+
+    println!(
+        "{} {} words, {:.2}s of video at {}, font {}",
+        log::done_mark(),
+        timeline.words.len(),
+        timeline.total_duration,
+        resolution,
+        font_location
+    );
+
+    Ok(())
+}
+
+// Render the video, returning None when only words, subtitles, or the command were requested
+pub fn generate_video(mut args: crate::Args) -> Result<Option<crate::Output>> {
+    let start = Instant::now();
+    // Catch bad options before reading (possibly piped) input
+    let resolution = validate_args(&args)?;
+
+    // Extract owned values that will be moved
+    let text_opt = args.text.take();
+    let bgm_opt = std::mem::take(&mut args.bgm_location);
+    let font_opt = args.font_location.take();
+
+    // Compute word timings, after the countdown if any
+    let mut timeline = build_timeline(text_opt, &args)?;
+    let word_count = timeline.words.len();
+
+    if args.orp && timeline.words.iter().any(|word| is_rtl(&word.text)) {
+        info!("Warning: right-to-left words are drawn whole, without --orp alignment");
+    }
+    let seconds_per_word = seconds_per_word(&args);
+    let countdown = args.countdown as f64;
+
+    // Preview segmentation without rendering
+    if args.print_words {
+        for (i, (start_time, end_time, word)) in timeline.timings.iter().enumerate() {
+            println!(
+                "{:>5}  {:>8.2}s  {:>8.2}s  {}",
+                i, start_time, end_time, word
+            );
+        }
+        return Ok(None);
+    }
+
+    // Export word timings as subtitles
+    if let Some(path) = &args.srt {
+        std::fs::write(path, srt::build_srt(&timeline.timings))
+            .with_context(|| format!("Failed to write subtitles to {}", path.display()))?;
+        info!("{} Subtitles written: {}", log::done_mark(), path.display());
+    }
+
+    if args.srt_only {
+        return Ok(None);
+    }
+
+    limit_timeline(&mut timeline, &args)?;
+    let Timeline {
+        words,
+        timings,
+        words_duration,
+        total_duration,
+    } = timeline;
+
+    // A preview encodes as fast as possible
+    if args.preview.is_some() {
+        if matches!(args.vcodec.as_str(), "libx264" | "libx265") {
+            args.preset = Some("ultrafast".to_string());
+        }
+        info!("Previewing the first {:.1}s", total_duration);
+    }
+
+    // Fail before any work if the output exists; FFmpeg itself always writes a temporary file
+    if !args.overwrite && !args.dry_run && Path::new(&args.output).exists() {
+        bail!(
+            "Output file {} already exists. Use --overwrite (-y) to replace it",
+            args.output
+        );
+    }
+
     // Get font location
-    let font_location = font_opt
-        .or_else(|| FontConfig::get_default_font().ok())
-        .context("No font available. Provide --font-location")?;
+    // Detection errors explain what to install, so pass them through to main
+    let (font_location, font_source) = match font_opt {
+        Some(font) => (font, "--font-location"),
+        None => (
+            FontConfig::get_default_font().context("No font available")?,
+            "OS default",
+        ),
+    };
+
+    info!("Using font: {} ({})", font_location, font_source);
+
+    // Catch a missing or unreadable font before building the whole filter chain
+    if !args.dry_run {
+        check_font(&font_location)?;
+    }
+
+    // Validate BGM (takes ownership)
+    let mut bgm_locations = validate_bgm(bgm_opt, args.bgm_from_config)?;
+
+    // All randomness comes from one RNG so --seed reproduces a run
+    let seed = args.seed.unwrap_or_else(rand::random);
+    debug!("Random seed: {}", seed);
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    if args.bgm_shuffle {
+        bgm_locations.shuffle(&mut rng);
+    }
+
+    if bgm_locations.is_empty() && (args.bgm_fade_in.is_some() || args.bgm_fade_out.is_some()) {
+        info!("Warning: BGM fade options are ignored without BGM");
+    }
+
+    if let Some(image) = args.background_image.take() {
+        args.background_image = validate_background_image(image);
+    }
+    if args.background_image.is_some() && args.bg_gradient.is_some() {
+        info!("Warning: --bg-gradient is ignored with a background image");
+    }
+
+    // Replace the video codec with a detected hardware encoder
+    if args.hwenc == HwEncode::Auto {
+        args.vcodec = detect_hw_encoder().to_string();
+        info!("Using encoder: {}", args.vcodec);
+    }
+
+    // Pick codecs that fit the container; H.264 can't go in WebM
+    let container = Container::resolve(args.format, &args.output)?;
+    if container == Container::Webm && args.vcodec == "libx264" {
+        args.vcodec = "libvpx-vp9".to_string();
+        info!("Using encoder: {} for WebM output", args.vcodec);
+    }
+    if container == Container::Webm && args.acodec == "aac" {
+        args.acodec = container.audio_codec().to_string();
+    }
+    if !container.supports_vcodec(&args.vcodec) {
+        bail!(
+            "Encoder '{}' can't be written to {}. Use a VP8, VP9 or AV1 encoder, or an .mp4/.mkv output",
+            args.vcodec,
+            args.output
+        );
+    }
+
+    if !container.supports_acodec(&args.acodec) {
+        bail!(
+            "Audio encoder '{}' can't be written to {}. Use an Opus or Vorbis encoder, or an .mp4/.mkv output",
+            args.acodec,
+            args.output
+        );
+    }
+
+    // GIFs are silent, so drop every audio source
+    if container == Container::Gif {
+        if !bgm_locations.is_empty() || args.tts.is_some() {
+            info!("Warning: GIF output has no audio, ignoring BGM and narration");
+        }
+        bgm_locations.clear();
+        args.tts = None;
+
+        if args.fps > GIF_MAX_FPS {
+            info!("Capping GIF frame rate at {} fps", GIF_MAX_FPS);
+        }
+    }
+
+    info!("Creating video: {}", args.output);
+    info!(
+        "Words: {} | WPM: {} | Duration per word: {:.2}s",
+        word_count,
+        effective_wpm(&args),
+        seconds_per_word
+    );
+
+    // Pick a renderer
+    let use_ass = match args.renderer {
+        Renderer::Ass => true,
+        Renderer::Drawtext => false,
+        Renderer::Auto => word_count > args.ass_word_threshold,
+    };
+
+    if use_ass && args.orp {
+        info!("Warning: --orp is only supported by the drawtext renderer");
+    }
+
+    if use_ass && args.vertical {
+        info!("Warning: --vertical is only supported by the drawtext renderer");
+    }
+
+    if use_ass && args.context_words {
+        info!("Warning: --context-words is only supported by the drawtext renderer");
+    }
+
+    // Synthesize narration, stretched to the words and starting after the countdown
+    let narration = match args.tts.as_deref() {
+        Some(_) if args.dry_run => {
+            info!(
+                "Warning: narration isn't synthesized on a dry run and is left out of the command"
+            );
+            None
+        }
+        Some(engine) => tts::synthesize(
+            engine,
+            args.tts_command.as_deref(),
+            &words
+                .iter()
+                .map(|word| word.text.as_str())
+                .collect::<Vec<_>>()
+                .join(" "),
+            words_duration,
+        )
+        .map(|mut narration| {
+            narration.delay = countdown;
+            narration
+        }),
+        None => None,
+    };
 
-    println!("Using font: {}", font_location);
+    // Parallel slices need per-frame expressions that only see their own slice,
+    // so the progress bar and GIF palette keep to one process
+    let jobs = if args.jobs > 1 && container == Container::Gif {
+        info!("Warning: --jobs is ignored for GIF output");
+        1
+    } else if args.jobs > 1 && args.show_progress_overlay {
+        info!("Warning: --jobs is ignored with --show-progress-overlay");
+        1
+    } else {
+        args.jobs.min(timings.len().max(1))
+    };
 
-    // Validate BGM (takes ownership)
-    let bgm_location = validate_bgm(bgm_opt)?;
+    let output = if jobs > 1 {
+        let segments =
+            segments::split_segments(&timings, args.countdown as usize, total_duration, jobs);
+        let parts = segments::render(
+            &args,
+            &segments,
+            &font_location,
+            &resolution,
+            container,
+            use_ass,
+        )?;
+
+        let list_path =
+            std::env::temp_dir().join(format!("src-cli-{}-segments.txt", std::process::id()));
+        let mut cmd = segments::concat_command(
+            &args,
+            &list_path,
+            &bgm_locations,
+            narration.as_ref(),
+            total_duration,
+            container,
+        );
 
-    // Validate colors
-    validate_color(&args.text_color).context("Invalid text color")?;
-    validate_color(&args.bg_color).context("Invalid background color")?;
-    validate_color(&args.secondary_color).context("Invalid secondary color")?;
+        if let Err(err) = std::fs::write(&list_path, segments::concat_list(&parts)) {
+            segments::remove_parts(&parts);
+            return Err(err).with_context(|| format!("Failed to write {}", list_path.display()));
+        }
 
-    // Get input text from argument or stdin
+        // Print the join after the segment commands (the list is kept, so they run as printed)
+        if args.dry_run {
+            println!("{}", format_command(&cmd));
+            return Ok(None);
+        }
 
-    let text = text_opt.map(Ok).unwrap_or_else(get_piped_input)?;
+        debug!("Running: {}", format_command(&cmd));
+        let output = run_with_progress(&mut cmd, total_duration);
+        segments::remove_parts(&parts);
+        let _ = std::fs::remove_file(&list_path);
+        output
+    } else {
+        // Long inputs render through a single subtitles filter, which parses much faster
+        let subtitle_file = if use_ass {
+            let path = std::env::temp_dir().join(format!("src-cli-{}.ass", std::process::id()));
+            let script = ass::build_subtitles(&timings, &args, &font_location, &resolution);
+            std::fs::write(&path, script)
+                .with_context(|| format!("Failed to write subtitles to {}", path.display()))?;
+            info!("Using ASS renderer: {}", path.display());
+            Some(path)
+        } else {
+            None
+        };
 
-    // Process words
-    let words = split_text(&text);
-    let word_count = words.len();
-    let seconds_per_word = 60.0 / args.wpm as f64;
+        // Build filters
+        let filters = build_filters(
+            &timings,
+            &args,
+            &font_location,
+            &resolution,
+            subtitle_file.as_deref(),
+        );
+        let filter_chain = filters.join(",");
+        debug!("Filter chain: {}", filter_chain);
 
-    println!("Creating video: {}", args.output);
-    println!(
-        "Words: {} | WPM: {} | Duration per word: {:.2}s",
-        word_count, args.wpm, seconds_per_word
-    );
+        info!(
+            "Estimated render time: ~{:.0}s (rough)",
+            estimate_render_seconds(total_duration, &args, &resolution, filters.len())
+        );
 
-    // Build filters
-    let (filters, total_duration) = build_filters(
-        &words,
-        args.wpm,
-        &args.text_color,
-        &args.secondary_color,
-        args.rest_duration,
-        args.focus_lines,
-        &font_location,
-    );
-    let filter_chain = filters.join(",");
+        let mut cmd = build_ffmpeg_command(
+            &args,
+            &bgm_locations,
+            narration.as_ref(),
+            &filter_chain,
+            total_duration,
+            &resolution,
+            container,
+        );
 
-    println!("Rendering video...");
+        // Print the command instead of running it (temporary files are kept for reuse)
+        if args.dry_run {
+            println!("{}", format_command(&cmd));
+            return Ok(None);
+        }
 
-    // Execute FFmpeg
-    let mut cmd = build_ffmpeg_command(
-        &args.output,
-        &args.bg_color,
-        bgm_location.as_deref(),
-        &filter_chain,
-        total_duration,
-        args.overwrite_output_file.unwrap_or(false),
-    );
-    let output = cmd
-        .output()
-        .context("Failed to execute ffmpeg. Is it installed?")?;
+        debug!("Running: {}", format_command(&cmd));
+
+        // Execute FFmpeg
+        let mut output = run_with_progress(&mut cmd, total_duration);
+
+        // Hardware encoding often fails on headless machines; retry once in software
+        let software_vcodec = if container == Container::Webm {
+            "libvpx-vp9"
+        } else {
+            "libx264"
+        };
+        if let Ok(failed) = &output
+            && !failed.status.success()
+            && is_encoder_failure(&String::from_utf8_lossy(&failed.stderr))
+            && (args.vcodec != software_vcodec || args.hwaccel != "none")
+            && container != Container::Gif
+        {
+            info!(
+                "Warning: Encoder {} failed, retrying with {} and no hardware acceleration",
+                args.vcodec, software_vcodec
+            );
+            args.vcodec = software_vcodec.to_string();
+            args.hwaccel = "none".to_string();
+            // Presets are encoder-specific (e.g. p1-p7 for NVENC)
+            args.preset = None;
+
+            cmd = build_ffmpeg_command(
+                &args,
+                &bgm_locations,
+                narration.as_ref(),
+                &filter_chain,
+                total_duration,
+                &resolution,
+                container,
+            );
+            debug!("Running: {}", format_command(&cmd));
+            output = run_with_progress(&mut cmd, total_duration);
+        }
+
+        // Clean up temporary files regardless of the outcome
+        if let Some(path) = &subtitle_file {
+            let _ = std::fs::remove_file(path);
+        }
+        output
+    };
+
+    // FFmpeg is done with the narration track, so remove it now
+    drop(narration);
+
+    let partial = partial_path(&args.output);
+    let output = output.context("Failed to execute ffmpeg. Is it installed?")?;
 
     if !output.status.success() {
+        let _ = std::fs::remove_file(&partial);
         let stderr = String::from_utf8_lossy(&output.stderr);
         bail!("FFmpeg failed:\n{}", stderr);
     }
 
+    // The file can appear after the up-front check, e.g. from a parallel run
+    if !args.overwrite && Path::new(&args.output).exists() {
+        let _ = std::fs::remove_file(&partial);
+        bail!(
+            "Output file {} already exists. Use --overwrite (-y) to replace it",
+            args.output
+        );
+    }
+    std::fs::rename(&partial, &args.output)
+        .with_context(|| format!("Failed to move {} to {}", partial, args.output))?;
+
     let duration = start.elapsed();
-    println!(
-        "✓ Video created: {} in {:.2}s (total video: {:.2}s)",
+    let render_seconds = duration.as_secs_f64();
+    info!(
+        "{} Video created: {} in {:.2}s (total video: {:.2}s, font: {})",
+        log::done_mark(),
         args.output,
         duration.as_secs_f64(),
-        total_duration
+        total_duration,
+        font_location
     );
 
-    Ok(())
+    Ok(Some(crate::Output {
+        word_count,
+        wpm: effective_wpm(&args),
+        seconds_per_word,
+        total_duration,
+        output_path: args.output.into(),
+        font_location,
+        render_seconds,
+        realtime_factor: total_duration / render_seconds,
+        words_per_second: word_count as f64 / render_seconds,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jieba_rs::Jieba;
+
+    #[test]
+    fn test_slice_words() {
+        let words: Vec<String> = ["a", "b", "c", "d"].map(String::from).to_vec();
+
+        assert_eq!(
+            slice_words(words.clone(), Some(1), Some(3)).unwrap(),
+            vec!["b", "c"]
+        );
+        assert_eq!(
+            slice_words(words.clone(), Some(2), None).unwrap(),
+            vec!["c", "d"]
+        );
+        assert_eq!(slice_words(words.clone(), None, None).unwrap(), words);
+        assert!(slice_words(words.clone(), Some(3), Some(3)).is_err());
+        assert!(slice_words(words.clone(), Some(0), Some(5)).is_err());
+        assert!(slice_words(words.clone(), Some(4), None).is_err());
+        // Without --end-word the error is about the start, not a made-up range
+        let err = slice_words(words, Some(500), None).unwrap_err().to_string();
+        assert_eq!(err, "Invalid start word 500. The text only has 4 words");
+    }
+
+    #[test]
+    fn test_escape_drawtext() {
+        // '%' would start a %{...} expansion
+        assert_eq!(escape_drawtext("100%"), r"100\\%");
+        // Option separators and backslashes are escaped for both remaining parsers
+        assert_eq!(escape_drawtext("12:30"), r"12\:30");
+        assert_eq!(escape_drawtext(r"a\b"), r"a\\\\b");
+        // A quote closes the quoted value, adds an escaped quote, and reopens it
+        assert_eq!(escape_drawtext("it's"), r"it\'\''s");
+        // Filtergraph separators are protected by the surrounding quotes
+        assert_eq!(escape_drawtext("[note],"), "[note],");
+        assert_eq!(escape_drawtext("300 wpm"), "300 wpm");
+    }
+
+    #[test]
+    fn test_hex_color_with_alpha() {
+        assert!(validate_color("#ffffee").is_ok());
+        assert!(validate_color("#ffffee80").is_ok());
+        assert!(validate_color("0xffffee80").is_ok());
+    }
+
+    #[test]
+    fn test_hex_color_invalid_length() {
+        assert!(validate_color("#ffff").is_err());
+        assert!(validate_color("#ffffee8").is_err());
+    }
+
+    #[test]
+    fn test_rgb_colors() {
+        assert!(validate_color("rgb(255,0,0)").is_ok());
+        assert!(validate_color("rgba(0,0,0,0.5)").is_ok());
+    }
+
+    #[test]
+    fn test_rgb_invalid_components() {
+        assert!(validate_color("rgb(300,0)").is_err());
+        assert!(validate_color("rgb(300,0,0)").is_err());
+        assert!(validate_color("rgba(0,0,0,1.5)").is_err());
+        assert!(validate_color("rgb(a,b,c)").is_err());
+    }
+
+    #[test]
+    fn test_typed_errors() {
+        assert!(matches!(
+            validate_color("nope"),
+            Err(Error::InvalidColor { color, .. }) if color == "nope"
+        ));
+
+        let err = validate_args(&parse_args(&["--text-color", "rgb(300,0,0)"])).unwrap_err();
+        assert!(matches!(Error::from(err), Error::InvalidColor { .. }));
+
+        let err = validate_bgm(vec!["missing.webm".to_string()], false).unwrap_err();
+        assert!(matches!(Error::from(err), Error::BgmMissing(paths) if paths == ["missing.webm"]));
+        // A default from the config is skipped instead
+        assert!(
+            validate_bgm(vec!["missing.webm".to_string()], true)
+                .unwrap()
+                .is_empty()
+        );
+
+        let err = check_font("missing.ttf").unwrap_err();
+        assert!(matches!(Error::from(err), Error::FontNotFound(_)));
+
+        let err = read_words(Some("  ".to_string()), &parse_args(&[])).unwrap_err();
+        assert!(matches!(Error::from(err), Error::EmptyInput(_)));
+    }
+
+    fn parse_args(cli: &[&str]) -> crate::Args {
+        use clap::Parser;
+        crate::Args::parse_from(std::iter::once("src-cli").chain(cli.iter().copied()))
+    }
+
+    // Words followed by a rest period
+    fn rested_words(args: &crate::Args, text: &str) -> Vec<String> {
+        let seconds_per_word = 60.0 / args.wpm as f64;
+        let jieba = has_cjk(text).then(Jieba::new);
+        let (timings, _) =
+            compute_timings(&split_text(text, jieba.as_ref(), &args.abbreviations), args);
+        timings
+            .into_iter()
+            .filter(|(start, end, _)| end - start > seconds_per_word + 1e-9)
+            .map(|(_, _, word)| word)
+            .collect()
+    }
+
+    #[test]
+    fn test_abbreviations_do_not_rest() {
+        let args = parse_args(&[]);
+        let rested = rested_words(
+            &args,
+            "Dr. Smith went to the U.S. yesterday. Then e.g. nothing.",
+        );
+
+        assert_eq!(rested, vec!["yesterday.", "nothing."]);
+    }
+
+    #[test]
+    fn test_custom_abbreviations() {
+        let args = parse_args(&["--abbreviations", "approx,Dept"]);
+        let rested = rested_words(&args, "It took approx. two days at the Dept. office.");
+
+        assert_eq!(rested, vec!["office."]);
+    }
+
+    #[test]
+    fn test_periodic_rest() {
+        let args = parse_args(&["--periodic-rest-interval", "60"]);
+        let word = Token {
+            text: "word".to_string(),
+            ends_sentence: false,
+            ends_clause: false,
+            ends_paragraph: false,
+        };
+        let words = vec![word; 1000];
+        let seconds_per_word = 60.0 / args.wpm as f64;
+
+        let (timings, _) = compute_timings(&words, &args);
+        let rest_starts: Vec<f64> = timings
+            .iter()
+            .filter(|(start, end, _)| end - start > seconds_per_word + 1e-9)
+            .map(|(start, _, _)| *start)
+            .collect();
+
+        // 1000 words at 300 wpm is ~200s, so three pauses roughly a minute apart
+        assert_eq!(rest_starts.len(), 3);
+        let mut previous = 0.0;
+        for start in rest_starts {
+            assert!((start - previous - 60.0).abs() < 1.0);
+            previous = start;
+        }
+    }
+
+    #[test]
+    fn test_chunked_timings() {
+        let args = parse_args(&["--chunk-size", "3"]);
+        let words = split_text(
+            "One two three four. Five six seven eight nine",
+            None,
+            &args.abbreviations,
+        );
+        let seconds_per_word = 60.0 / args.wpm as f64;
+
+        let (timings, total_duration) = compute_timings(&words, &args);
+        let chunks: Vec<&str> = timings.iter().map(|(_, _, chunk)| chunk.as_str()).collect();
+
+        // The sentence end closes a chunk early
+        assert_eq!(
+            chunks,
+            vec!["One two three", "four.", "Five six seven", "eight nine"]
+        );
+
+        // Display time scales with the words in each chunk, plus one rest
+        let expected = seconds_per_word * words.len() as f64 + args.rest_duration;
+        assert!((total_duration - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_word_gap() {
+        let words = split_text("One two. Three", None, &[]);
+        let (plain, plain_duration) = compute_timings(&words, &parse_args(&["--wpm", "300"]));
+        let (gapped, duration) =
+            compute_timings(&words, &parse_args(&["--wpm", "300", "--word-gap", "0.05"]));
+
+        // Same pacing, each word ends 50ms earlier
+        assert_eq!(duration, plain_duration);
+        for ((start, end, _), (plain_start, plain_end, _)) in gapped.iter().zip(&plain) {
+            assert_eq!(start, plain_start);
+            assert!((plain_end - end - 0.05).abs() < 1e-9);
+        }
+
+        // A gap longer than a word is clamped to leave one frame
+        let (clamped, _) =
+            compute_timings(&words, &parse_args(&["--wpm", "300", "--word-gap", "5"]));
+        let (start, end, _) = &clamped[0];
+        assert!((end - start - 1.0 / 30.0).abs() < 1e-9);
+        assert!(validate_args(&parse_args(&["--word-gap=-1"])).is_err());
+    }
+
+    #[test]
+    fn test_cjk_sentence_rest() {
+        let args = parse_args(&[]);
+        let rested = rested_words(&args, "今天天氣很好。我們去公園散步吧！你覺得怎麼樣？好的");
+
+        assert_eq!(rested, vec!["好。", "吧！", "怎麼樣？"]);
+    }
+
+    #[test]
+    fn test_finite_bgm_is_padded() {
+        let args = parse_args(&["--bgm-loop", "0"]);
+        let track = |audio_stream| Bgm {
+            path: "bgm.mp3".to_string(),
+            audio_stream,
+        };
+        let (graph, _) = build_audio_graph(&args, &[track(None)], None, 10.0).unwrap();
+        assert_eq!(graph, "[1:a:0]apad[bgm]");
+
+        let args = parse_args(&["--bgm-loop", "2"]);
+        let (graph, _) =
+            build_audio_graph(&args, &[track(Some(0)), track(None)], None, 10.0).unwrap();
+        assert!(graph.contains("aloop=loop=2:"));
+        assert!(graph.ends_with("apad[bgm]"));
+    }
+
+    #[test]
+    fn test_font_size_auto() {
+        let long_word = "a".repeat(40);
+
+        // Fixed sizes by default, even if the word overflows
+        let args = parse_args(&[]);
+        assert_eq!(word_fontsize(&long_word, &args, 1920), 100);
+
+        // 40 chars * 0.6em must fit in 90% of 1920px
+        let args = parse_args(&["--font-size-auto"]);
+        assert_eq!(word_fontsize(&long_word, &args, 1920), 72);
+        assert_eq!(word_fontsize("short", &args, 1920), 100);
+        assert_eq!(word_fontsize("這是一個很長的中文詞語", &args, 1080), 88);
+    }
+
+    #[test]
+    fn test_output_container() {
+        assert_eq!(Container::from_output("out.MP4").unwrap(), Container::Mp4);
+        assert_eq!(Container::from_output("out.webm").unwrap(), Container::Webm);
+        assert!(Container::from_output("out.avi").is_err());
+        assert!(Container::from_output("out").is_err());
+
+        assert!(Container::Webm.supports_vcodec("libvpx-vp9"));
+        assert!(Container::Webm.supports_vcodec("libsvtav1"));
+        assert!(!Container::Webm.supports_vcodec("h264_nvenc"));
+        assert!(Container::Mkv.supports_vcodec("h264_nvenc"));
+    }
+
+    #[test]
+    fn test_gif_command_has_no_audio() {
+        let args = parse_args(&["-o", "clip.gif", "--fps", "30"]);
+        let cmd = build_ffmpeg_command(
+            &args,
+            &[],
+            None,
+            "null",
+            2.0,
+            &Resolution::default(),
+            Container::Gif,
+        );
+        let command = format_command(&cmd);
+
+        assert!(command.contains(":r=15"));
+        assert!(command.contains("palettegen"));
+        assert!(!command.contains("-c:v"));
+        assert!(!command.contains("-c:a"));
+    }
+
+    #[test]
+    fn test_watermark() {
+        let timings = [(0.0, 0.2, "word".to_string())];
+        let resolution = Resolution::default();
+        let watermark = |cli: &[&str]| {
+            let args = parse_args(cli);
+            build_filters(&timings, &args, "font.ttf", &resolution, None)
+                .into_iter()
+                .find(|filter| filter.contains("fontsize=60"))
+        };
+
+        let default = watermark(&[]).unwrap();
+        assert!(default.contains("text='300 wpm'"));
+        assert!(default.contains("x=(w-text_w)*0.9:y=(h-text_h)*0.9"));
+
+        let custom = watermark(&[
+            "--watermark",
+            "custom",
+            "--watermark-text",
+            "@me",
+            "--watermark-position",
+            "top-left",
+        ])
+        .unwrap();
+        assert!(custom.contains("text='@me'"));
+        assert!(custom.contains("x=(w-text_w)*0.1:y=(h-text_h)*0.1"));
+
+        assert!(watermark(&["--watermark", "none"]).is_none());
+    }
+
+    #[test]
+    fn test_background_image_input() {
+        let args = parse_args(&["--background-image", "photo.jpg"]);
+        let cmd = build_ffmpeg_command(
+            &args,
+            &[],
+            None,
+            "null",
+            2.0,
+            &Resolution::default(),
+            Container::Mp4,
+        );
+        let command = format_command(&cmd);
+
+        assert!(command.contains("-loop 1 -framerate 30 -t 2 -i photo.jpg"));
+        assert!(command.contains("-vf scale=1920:1080:force_original_aspect_ratio=increase"));
+        assert!(!command.contains("lavfi"));
+    }
+
+    #[test]
+    fn test_background_styling() {
+        let args = parse_args(&[
+            "--bg-color",
+            "navy",
+            "--bg-gradient",
+            "#000000",
+            "--vignette",
+        ]);
+        let command = format_command(&build_ffmpeg_command(
+            &args,
+            &[],
+            None,
+            "null",
+            2.0,
+            &Resolution::default(),
+            Container::Mp4,
+        ));
+        assert!(command.contains("-i 'gradients=s=1920x1080:d=2:r=30:c0=navy:c1=#000000:"));
+        assert!(!command.contains("color=c="));
+
+        // The vignette comes before every drawtext
+        let filters = build_filters(&[], &args, "font.ttf", &Resolution::default(), None);
+        assert_eq!(filters[0], "vignette");
+
+        // Only the background is blurred: the blur leads the chain, ahead of the words
+        let args = parse_args(&["--blur-background", "8", "--vignette"]);
+        let filters = build_filters(
+            &[(0.0, 1.0, "word".to_string())],
+            &args,
+            "font.ttf",
+            &Resolution::default(),
+            None,
+        );
+        assert_eq!(filters[..2], ["gblur=sigma=8", "vignette"]);
+        assert!(filters.last().unwrap().starts_with("drawtext="));
+        assert!(validate_args(&parse_args(&["--blur-background", "0"])).is_err());
+
+        assert!(validate_args(&parse_args(&["--bg-gradient", "nope"])).is_err());
+    }
+
+    #[test]
+    fn test_prepare_fontfile() {
+        assert_eq!(
+            prepare_fontfile("/usr/share/fonts/DejaVuSans.ttf"),
+            "/usr/share/fonts/DejaVuSans.ttf"
+        );
+        // The drive colon would otherwise end the fontfile option
+        assert_eq!(
+            prepare_fontfile("C:/Windows/Fonts/msyh.ttc"),
+            r"C\:/Windows/Fonts/msyh.ttc"
+        );
+        assert_eq!(prepare_fontfile("/fonts/it's.ttf"), r"/fonts/it\'\''s.ttf");
+
+        if cfg!(windows) {
+            assert_eq!(
+                prepare_fontfile(r"D:\Fonts\custom.ttf"),
+                r"D\:/Fonts/custom.ttf"
+            );
+        }
+    }
+
+    #[test]
+    fn test_pause_weights() {
+        let args = parse_args(&["--comma-pause", "0.5", "--rest-duration", "0.2"]);
+        let seconds_per_word = 60.0 / args.wpm as f64;
+        let tokens = split_text(
+            "Start here, then stop. Next\n\nNew paragraph",
+            None,
+            &args.abbreviations,
+        );
+
+        let (timings, _) = compute_timings(&tokens, &args);
+        let pauses: Vec<(&str, f64)> = timings
+            .iter()
+            .map(|(start, end, word)| (word.as_str(), end - start - seconds_per_word))
+            .filter(|(_, pause)| *pause > 1e-9)
+            .collect();
+
+        assert_eq!(pauses.len(), 2);
+        assert_eq!(pauses[0].0, "here,");
+        assert!((pauses[0].1 - 0.1).abs() < 1e-9);
+        assert_eq!(pauses[1].0, "stop.");
+        assert!((pauses[1].1 - 0.2).abs() < 1e-9);
+
+        // The paragraph pause is a blank gap before the next word
+        let gap = timings[5].0 - timings[4].1;
+        assert_eq!(timings[4].2, "Next");
+        assert!((gap - 0.4).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_countdown_offsets_words() {
+        let timings = vec![
+            (0.0, 0.2, "Hello".to_string()),
+            (0.2, 0.5, "world.".to_string()),
+        ];
+        let timings = add_countdown(timings, 3);
+
+        let words: Vec<&str> = timings.iter().map(|(_, _, word)| word.as_str()).collect();
+        assert_eq!(words, vec!["3", "2", "1", "Hello", "world."]);
+        assert_eq!(timings[0].0, 0.0);
+        assert_eq!(timings[2].1, 3.0);
+        assert!((timings[3].0 - 3.0).abs() < 1e-9);
+        assert!((timings[4].1 - 3.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_truncate_timings_for_preview() {
+        let timings = vec![
+            (0.0, 1.0, "one".to_string()),
+            (1.0, 2.5, "two".to_string()),
+            (2.5, 3.0, "three".to_string()),
+        ];
+
+        let preview = truncate_timings(timings.clone(), 2.0);
+        assert_eq!(
+            preview,
+            vec![(0.0, 1.0, "one".to_string()), (1.0, 2.0, "two".to_string())]
+        );
+        assert_eq!(truncate_timings(timings.clone(), 10.0), timings);
+        assert!(validate_args(&parse_args(&["--preview", "0"])).is_err());
+    }
+
+    #[test]
+    fn test_split_segments() {
+        let timings = add_countdown(
+            (0..6)
+                .map(|i| (i as f64, (i + 1) as f64, format!("w{}", i)))
+                .collect(),
+            2,
+        );
+
+        let segments = segments::split_segments(&timings, 2, 9.0, 3);
+        let bounds: Vec<(f64, f64, usize)> = segments
+            .iter()
+            .map(|segment| (segment.start, segment.end, segment.timings.len()))
+            .collect();
+        assert_eq!(bounds, vec![(0.0, 3.0, 3), (3.0, 6.0, 3), (6.0, 9.0, 2)]);
+        // Each slice starts its own clock at zero
+        assert_eq!(segments[1].timings[0], (0.0, 1.0, "w1".to_string()));
+
+        // The countdown is never cut, and a slice always has words
+        let segments = segments::split_segments(&timings, 2, 9.0, 8);
+        assert_eq!(segments[0].timings.len(), 2);
+        assert!(segments.iter().all(|segment| !segment.timings.is_empty()));
+        assert!(validate_args(&parse_args(&["--jobs", "0"])).is_err());
+    }
+
+    #[test]
+    fn test_concat_command_adds_bgm_to_joined_output() {
+        let args = parse_args(&["--jobs", "2", "--output", "out.mp4"]);
+        let bgm = vec![Bgm {
+            path: "bgm.mp3".to_string(),
+            audio_stream: Some(0),
+        }];
+        let cmd = format_command(&segments::concat_command(
+            &args,
+            Path::new("list.txt"),
+            &bgm,
+            None,
+            10.0,
+            Container::Mp4,
+        ));
+
+        assert!(cmd.contains("-f concat -safe 0 -i list.txt -stream_loop -1 -i bgm.mp3"));
+        assert!(cmd.contains("-map 0:v:0 -filter_complex"));
+        assert!(cmd.contains("-c:v copy -y -f mp4 out.mp4.partial"));
+        assert_eq!(
+            segments::concat_list(&["/tmp/a".to_string(), "/tmp/it's".to_string()]),
+            "file '/tmp/a'\nfile '/tmp/it'\\''s'\n"
+        );
+    }
+
+    #[test]
+    fn test_outro_follows_last_word() {
+        let args = parse_args(&["--outro-text", "The End", "--outro-duration", "3"]);
+        let timings = [
+            (0.0, 0.2, "Hello".to_string()),
+            (0.2, 0.5, "world.".to_string()),
+        ];
+        let filters = build_filters(&timings, &args, "font.ttf", &Resolution::default(), None);
+
+        let outro = filters
+            .iter()
+            .find(|filter| filter.contains("text='The End'"))
+            .unwrap();
+        assert!(outro.contains("x=(w-text_w)/2:y=(h-text_h)/2"));
+        assert!(outro.contains("between(t,0.5,3.5)"));
+    }
+
+    #[test]
+    fn test_word_x_and_margin() {
+        assert!(validate_x_expression("(w-text_w)/2").is_ok());
+        assert!(validate_x_expression("").is_err());
+        assert!(validate_x_expression("(w-text_w").is_err());
+        assert!(validate_x_expression("w)/2(").is_err());
+        assert!(validate_x_expression("w':text='x").is_err());
+
+        let args = parse_args(&["--word-x", "(w-text_w)/2"]);
+        let filters = build_word_filter("word", &args, "font.ttf", 1920, 0.0, 1.0);
+        assert!(filters[0].contains(":x='(w-text_w)/2':"));
+        // Function arguments stay inside the option instead of splitting the chain
+        let args = parse_args(&["--word-x", "max(0,w*0.3-text_w)"]);
+        assert!(validate_args(&args).is_ok());
+        let chain = build_filters(
+            &[(0.0, 1.0, "word".to_string())],
+            &args,
+            "font.ttf",
+            &Resolution {
+                width: 1920,
+                height: 1080,
+            },
+            None,
+        )
+        .join(",");
+        assert!(chain.contains(":x='max(0,w*0.3-text_w)':"));
+
+        let args = parse_args(&["--align", "right"]);
+        let filters = build_word_filter("word", &args, "font.ttf", 1920, 0.0, 1.0);
+        assert!(filters[0].contains(":x='(w-text_w)*0.9':"));
+        // An explicit expression wins over the preset
+        let args = parse_args(&["--align", "center", "--word-x", "w*0.3"]);
+        let filters = build_word_filter("word", &args, "font.ttf", 1920, 0.0, 1.0);
+        assert!(filters[0].contains(":x='w*0.3':"));
+        // Even when it spells out the default position
+        let args = parse_args(&["--align", "right", "--word-x", "(w-text_w)/5*2"]);
+        let filters = build_word_filter("word", &args, "font.ttf", 1920, 0.0, 1.0);
+        assert!(filters[0].contains(":x='(w-text_w)/5*2':"));
+
+        // 10 characters at 0.6em are 6 ems wide
+        let args = parse_args(&["--font-size-auto", "--fontsize", "500", "--margin", "60"]);
+        assert_eq!(word_fontsize("abcdefghij", &args, 1920), 300);
+
+        // Huge margins are rejected instead of overflowing
+        let args = parse_args(&["--margin", "4000000000"]);
+        assert!(validate_args(&args).is_err());
+        assert_eq!(usable_width_tenths(&args, 1920), 0);
+    }
+
+    #[test]
+    fn test_output_goes_to_partial_file() {
+        let command = |cli: &[&str]| {
+            format_command(&build_ffmpeg_command(
+                &parse_args(cli),
+                &[],
+                None,
+                "null",
+                2.0,
+                &Resolution::default(),
+                Container::Mp4,
+            ))
+        };
+
+        // The temporary file is always replaced, so FFmpeg never prompts
+        assert!(command(&[]).ends_with(" -y -f mp4 output.mp4.partial"));
+        assert!(command(&["--overwrite"]).ends_with(" -y -f mp4 output.mp4.partial"));
+    }
+
+    #[test]
+    fn test_bgm_from_video_uses_first_audio_stream() {
+        // Video first, then a subtitle track before the audio
+        let streams = "0,video\n1,subtitle\n2,audio\n3,audio\n";
+        assert_eq!(first_audio_stream(streams), Some(2));
+        assert_eq!(first_audio_stream("0,video\n1,subtitle\n"), None);
+
+        let args = parse_args(&[]);
+        let bgm = Bgm {
+            path: "clip.mkv".to_string(),
+            audio_stream: Some(2),
+        };
+        let (graph, _) = build_audio_graph(&args, &[bgm], None, 10.0).unwrap();
+        assert!(graph.starts_with("[1:2]"));
+    }
+
+    #[test]
+    fn test_context_words() {
+        let args = parse_args(&[
+            "--context-words",
+            "--text-color",
+            "#ffffee",
+            "--countdown",
+            "1",
+        ]);
+        let timings = add_countdown(
+            vec![
+                (0.0, 0.2, "one".to_string()),
+                (0.2, 0.4, "two".to_string()),
+                (0.4, 0.6, "three".to_string()),
+            ],
+            args.countdown,
+        );
+        let filters = build_context_filters(&timings, &args, "font.ttf", 1920);
+
+        // "one" has only a next word, "three" only a previous one, "1" none
+        assert_eq!(filters.len(), 4);
+        assert!(filters[0].contains("text='two'") && filters[0].contains("x=w*0.8"));
+        assert!(filters[0].contains("fontcolor=#FFFFEE66"));
+        assert!(filters[0].contains("between(t,1,1.2)"));
+        assert!(filters[1].contains("text='one'") && filters[1].contains("x=w*0.2-text_w"));
+        assert!(filters.iter().all(|filter| !filter.contains("text='1'")));
+    }
+
+    #[test]
+    fn test_encoder_failure_detection() {
+        assert!(is_encoder_failure(
+            "[h264_nvenc @ 0x55] Cannot load libcuda.so.1\nError while opening encoder for output stream #0:0"
+        ));
+        assert!(!is_encoder_failure(
+            "photo.jpg: Invalid data found when processing input"
+        ));
+
+        let cmd = build_ffmpeg_command(
+            &parse_args(&["--hwaccel", "none"]),
+            &[],
+            None,
+            "null",
+            2.0,
+            &Resolution::default(),
+            Container::Mp4,
+        );
+        assert!(format_command(&cmd).contains("-hwaccel none"));
+    }
+
+    #[test]
+    fn test_decode_input() {
+        // "你好" in GBK and "こんにちは" in Shift_JIS
+        let gbk = [0xC4, 0xE3, 0xBA, 0xC3];
+        assert_eq!(decode_input(&gbk, Some("gbk")).unwrap(), "你好");
+        let sjis = [0x82, 0xB1, 0x82, 0xF1, 0x82, 0xC9, 0x82, 0xBF, 0x82, 0xCD];
+        assert_eq!(
+            decode_input(&sjis, Some("shift_jis")).unwrap(),
+            "こんにちは"
+        );
+
+        assert_eq!(decode_input("你好".as_bytes(), None).unwrap(), "你好");
+        assert!(decode_input(&gbk, Some("klingon")).is_err());
+    }
+
+    #[test]
+    fn test_epub_chapter_input() {
+        use std::io::Write;
+        use zip::write::SimpleFileOptions;
+
+        let path = std::env::temp_dir().join(format!("src-cli-test-{}.epub", std::process::id()));
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+        let entries = [
+            (
+                "META-INF/container.xml",
+                r#"<container><rootfiles><rootfile full-path="OEBPS/content.opf"/></rootfiles></container>"#,
+            ),
+            (
+                "OEBPS/content.opf",
+                r#"<package><manifest><item id="c2" href="Chapter%202.xhtml"/><item id="c1" href="one.xhtml"/></manifest><spine><itemref idref="c1"/><itemref idref="c2"/></spine></package>"#,
+            ),
+            (
+                "OEBPS/one.xhtml",
+                "<html><head><title>Skip</title></head><body><h1>One</h1></body></html>",
+            ),
+            (
+                "OEBPS/Chapter 2.xhtml",
+                "<html><head><style>p {}</style></head><body><h1>Two</h1>\n  <p>Fish &amp; chips,<br/>please.</p><p>Next&nbsp;one</p></body></html>",
+            ),
+        ];
+        for (name, content) in entries {
+            zip.start_file(name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let text = epub::read_chapter(&path, 2).unwrap();
+        let result = epub::read_chapter(&path, 3);
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(text, "Two\n\nFish & chips, please.\n\nNext one");
+        assert!(result.unwrap_err().to_string().contains("chapters 1 to 2"));
+    }
+
+    #[test]
+    fn test_validate_args() {
+        assert_eq!(
+            validate_args(&parse_args(&["--resolution", "1280x720"])).unwrap(),
+            Resolution {
+                width: 1280,
+                height: 720
+            }
+        );
+        assert!(validate_args(&parse_args(&["--fps", "0"])).is_err());
+        assert!(validate_args(&parse_args(&["--chunk-size", "0"])).is_err());
+        assert!(validate_args(&parse_args(&["--max-duration", "0"])).is_err());
+        assert!(validate_args(&parse_args(&["--watermark", "custom"])).is_err());
+    }
+
+    #[test]
+    fn test_check_times_the_rendered_words() {
+        let args = |extra: &[&str]| {
+            let mut cli = vec!["--text", "one two three four", "--wpm", "60"];
+            cli.extend(extra);
+            parse_args(&cli)
+        };
+
+        // Repeats count toward the length, as in the rendered video
+        let repeated = args(&["--repeat", "3", "--outro-text", "The End"]);
+        let timeline = build_timeline(repeated.text.clone(), &repeated).unwrap();
+        assert_eq!(timeline.words.len(), 12);
+        assert!(timeline.total_duration > 12.0);
+
+        // So an input over --max-duration fails the check, not just the render
+        let once = args(&["--max-duration", "10"]);
+        let mut timeline = build_timeline(once.text.clone(), &once).unwrap();
+        assert!(limit_timeline(&mut timeline, &once).is_ok());
+        let err = check(args(&["--repeat", "3", "--max-duration", "10"])).unwrap_err();
+        assert!(err.to_string().contains("over --max-duration 10s"));
+    }
+
+    #[test]
+    fn test_estimate_huge_resolution() {
+        let args = parse_args(&["--resolution", "70000x70000"]);
+        let resolution = validate_args(&args).unwrap();
+        assert!(estimate_render_seconds(1.0, &args, &resolution, 1) > 0.0);
+    }
+
+    #[test]
+    fn test_progress_overlay() {
+        let timings = vec![(0.0, 0.5, "a".to_string()), (0.5, 1.25, "b".to_string())];
+        let filters = |cli: &[&str]| {
+            build_filters(
+                &timings,
+                &parse_args(cli),
+                "font.ttf",
+                &Resolution::default(),
+                None,
+            )
+        };
+
+        assert!(!filters(&[]).iter().any(|f| f.contains("progress")));
+
+        let filters = filters(&["--show-progress-overlay", "--secondary-color", "gray"]);
+        let progress = filters.iter().find(|f| f.contains("progress")).unwrap();
+        assert!(progress.contains("crop=iw:6:0:ih-6"));
+        assert!(progress.contains("color=gray"));
+        assert!(progress.ends_with("overlay=x='-w+w*min(1,t/1.25)':y=H-h"));
+    }
+
+    #[test]
+    fn test_pixel_format() {
+        let command = |cli: &[&str]| {
+            format_command(&build_ffmpeg_command(
+                &parse_args(cli),
+                &[],
+                None,
+                "null",
+                2.0,
+                &Resolution::default(),
+                Container::Mp4,
+            ))
+        };
+
+        assert!(command(&[]).contains("-pix_fmt yuv420p "));
+        assert!(command(&["--pix-fmt", "yuv420p10le"]).contains("-pix_fmt yuv420p10le "));
+        assert!(validate_args(&parse_args(&["--pix-fmt", "yuv444p"])).is_ok());
+        assert!(validate_args(&parse_args(&["--pix-fmt", "yuv420p:x"])).is_err());
+    }
+
+    #[test]
+    fn test_audio_codec_and_bitrate() {
+        assert!(validate_bitrate("128k").is_ok());
+        assert!(validate_bitrate("96000").is_ok());
+        assert!(validate_bitrate("0k").is_err());
+        assert!(validate_bitrate("128kb").is_err());
+        assert!(validate_bitrate("fast").is_err());
+
+        assert!(Container::Webm.supports_acodec("libopus"));
+        assert!(!Container::Webm.supports_acodec("aac"));
+
+        let args = parse_args(&["--acodec", "libmp3lame", "--abitrate", "96k"]);
+        let bgm = Bgm {
+            path: "a.webm".to_string(),
+            audio_stream: None,
+        };
+        let command = format_command(&build_ffmpeg_command(
+            &args,
+            &[bgm],
+            None,
+            "null",
+            2.0,
+            &Resolution::default(),
+            Container::Mp4,
+        ));
+        assert!(command.contains("-c:a libmp3lame -b:a 96k"));
+    }
+
+    #[test]
+    fn test_seconds_per_word_override() {
+        let args = parse_args(&["--spw", "0.25"]);
+        assert_eq!(seconds_per_word(&args), 0.25);
+        assert_eq!(effective_wpm(&args), 240);
+
+        let words = split_text("one two three", None, &args.abbreviations);
+        let (timings, _) = compute_timings(&words, &args);
+        assert!((timings[2].0 - 0.5).abs() < 1e-9);
+
+        let args = parse_args(&["--wpm", "120"]);
+        assert_eq!(seconds_per_word(&args), 0.5);
+        assert_eq!(effective_wpm(&args), 120);
+        assert!(validate_args(&parse_args(&["--spw", "0"])).is_err());
+    }
+
+    #[test]
+    fn test_rtl_word_is_drawn_whole() {
+        // Known limitation: ORP alignment isn't applied to right-to-left words
+        let args = parse_args(&["--orp"]);
+        let filters = build_word_filter("مرحبا", &args, "font.ttf", 1920, 0.0, 1.0);
+
+        assert_eq!(filters.len(), 1);
+        assert!(filters[0].contains(":x='(w-text_w)/5*3':"));
+        assert!(filters[0].ends_with(":text_shaping=1"));
+    }
+
+    #[test]
+    fn test_vertical_cjk_word_is_stacked() {
+        let args = parse_args(&["--vertical", "--fontsize", "100"]);
+        let filters = build_word_filter("中文字", &args, "font.ttf", 1920, 0.0, 1.0);
+
+        assert_eq!(filters.len(), 3);
+        for (filter, (glyph, y)) in filters.iter().zip([("中", -100), ("文", 0), ("字", 100)]) {
+            assert!(filter.contains(&format!(":text='{}':", glyph)));
+            assert!(filter.contains(&format!(":y=h/2{:+}-ascent:", y)));
+        }
+
+        // Other scripts stay horizontal
+        let filters = build_word_filter("word", &args, "font.ttf", 1920, 0.0, 1.0);
+        assert_eq!(filters.len(), 1);
+        assert!(filters[0].contains(":text='word':"));
+    }
+
+    #[test]
+    fn test_auto_emphasize_colors_long_words() {
+        let args = parse_args(&["--auto-emphasize", "--emphasis-color", "orange"]);
+        let filters = build_word_filter("chlorophyll", &args, "font.ttf", 1920, 0.0, 1.0);
+        assert!(filters[0].contains(":fontcolor=orange:"));
+
+        let filters = build_word_filter("leaf", &args, "font.ttf", 1920, 0.0, 1.0);
+        assert!(filters[0].contains(&format!(":fontcolor={}:", args.text_color)));
+
+        let script = ass::build_subtitles(
+            &[(0.0, 1.0, "chlorophyll".to_string())],
+            &args,
+            "font.ttf",
+            &Resolution {
+                width: 1920,
+                height: 1080,
+            },
+        );
+        assert!(script.contains("\\1c&H0000A5FF&}chlorophyll"));
+    }
+
+    #[test]
+    fn test_ass_escapes_override_codes() {
+        let script = ass::build_subtitles(
+            &[(0.0, 1.0, "C:\\New{x}".to_string())],
+            &parse_args(&[]),
+            "font.ttf",
+            &Resolution {
+                width: 1920,
+                height: 1080,
+            },
+        );
+        assert!(script.contains("C:\\\u{2060}New\\{x\\}"));
+        assert!(!script.contains("C:\\N"));
+    }
+
+    #[test]
+    fn test_long_chunk_wraps() {
+        let args = parse_args(&["--chunk-size", "8"]);
+        let chunk = "reading several quite long words together overflows one line";
+
+        let lines = wrap_chunk(chunk, &args, 1920);
+        assert!((2..=MAX_CHUNK_LINES).contains(&lines.len()));
+        assert_eq!(lines.join(" "), chunk);
+        assert_eq!(wrap_chunk("two words", &args, 1920), vec!["two words"]);
+
+        let filters = build_word_filter(chunk, &args, "font.ttf", 1920, 0.0, 1.0);
+        assert!(filters[0].contains(&lines.join("\n")));
+        assert!(filters[0].contains(":y=(h-text_h)/2:"));
+
+        // Pivot alignment keeps the chunk on one line
+        let args = parse_args(&["--chunk-size", "8", "--orp"]);
+        let filters = build_word_filter(chunk, &args, "font.ttf", 1920, 0.0, 1.0);
+        assert!(filters.iter().all(|f| !f.contains('\n')));
+    }
+
+    #[test]
+    fn test_letter_and_line_spacing() {
+        assert_eq!(space_letters("abc", 0), "abc");
+        assert_eq!(space_letters("abc", 1), "a\u{2009}b\u{2009}c");
+        assert_eq!(space_letters("مرحبا", 2), "مرحبا");
+
+        let args = parse_args(&["--letter-spacing", "1", "--line-spacing", "-4"]);
+        let filters = build_word_filter("hi", &args, "font.ttf", 1920, 0.0, 1.0);
+        assert!(filters[0].contains("text='h\u{2009}i'"));
+        assert!(filters[0].contains(":line_spacing=-4:"));
+
+        // The thin spaces neither make the word long nor count as full letters
+        let args = parse_args(&["--letter-spacing", "3", "--font-size-auto"]);
+        let spaced = space_letters("abcdefghij", 3);
+        assert_eq!(word_fontsize(&spaced, &args, 1920), args.fontsize);
+
+        // With ORP the left part keeps the spacing up to the pivot
+        let args = parse_args(&["--orp", "--letter-spacing", "1"]);
+        let filters = build_word_filter("word", &args, "font.ttf", 1920, 0.0, 1.0);
+        assert!(filters[0].contains("text='w\u{2009}'"));
+        assert!(filters[1].contains("text='o\u{2009}r\u{2009}d'"));
+    }
+
+    #[test]
+    fn test_font_candidates() {
+        assert_eq!(FontConfig::candidates(Type::Ubuntu).len(), 3);
+        assert!(FontConfig::candidates(Type::Macos)[0].ends_with(".ttc"));
+        assert!(FontConfig::candidates(Type::Windows)[0].ends_with("/Fonts/msyh.ttc"));
+        assert!(FontConfig::candidates(Type::Arch).is_empty());
+    }
+
+    #[test]
+    fn test_focus_color_falls_back_to_secondary() {
+        let drawboxes = |cli: &[&str]| {
+            build_filters(
+                &[],
+                &parse_args(cli),
+                "font.ttf",
+                &Resolution::default(),
+                None,
+            )
+            .into_iter()
+            .filter(|filter| filter.starts_with("drawbox"))
+            .collect::<Vec<_>>()
+        };
+
+        assert!(
+            drawboxes(&["--secondary-color", "gray"])
+                .iter()
+                .all(|filter| filter.ends_with("color=gray"))
+        );
+        assert!(
+            drawboxes(&["--secondary-color", "gray", "--focus-color", "#00ff00"])
+                .iter()
+                .all(|filter| filter.ends_with("color=#00ff00"))
+        );
+    }
+
+    #[test]
+    fn test_reverse_words() {
+        let args = parse_args(&["--reverse"]);
+        let words = reverse_words(split_text(
+            "One two, three. Four\n\nFive",
+            None,
+            &args.abbreviations,
+        ));
+        let texts: Vec<&str> = words.iter().map(|word| word.text.as_str()).collect();
+        assert_eq!(texts, vec!["Five", "Four", "three.", "two,", "One"]);
+
+        // Pauses stay between the same pairs of words
+        assert!(words[0].ends_paragraph);
+        assert!(words[1].ends_sentence);
+        assert!(words[2].ends_clause);
+        assert!(!words[3].ends_clause && !words[4].ends_sentence);
+    }
+
+    #[test]
+    fn test_repeat_words() {
+        let args = parse_args(&["--repeat", "3"]);
+        let words = split_text("One two.", None, &args.abbreviations);
+        let (_, single) = compute_timings(&words, &args);
+
+        let repeated = repeat_words(words, args.repeat);
+        assert_eq!(repeated.len(), 6);
+        assert!(repeated[1].ends_paragraph && repeated[3].ends_paragraph);
+        assert!(!repeated[5].ends_paragraph);
+
+        // Time keeps running across passes, with a paragraph pause between them
+        let (timings, total) = compute_timings(&repeated, &args);
+        assert!(timings[2].0 > timings[1].1);
+        // Each pass ends a sentence; between passes the paragraph pause replaces it
+        let extra = args.rest_duration * (args.paragraph_pause - args.sentence_pause);
+        assert!((total - (single * 3.0 + extra * 2.0)).abs() < 1e-9);
+        assert!(validate_args(&parse_args(&["--repeat", "0"])).is_err());
+    }
 }
@@ -0,0 +1,110 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use super::{Resolution, color_to_rgb, space_letters, word_color, word_fontsize, wrap_chunk};
+
+// Format seconds as an ASS timestamp (H:MM:SS.cc)
+fn format_timestamp(seconds: f64) -> String {
+    let centis = (seconds * 100.0).round() as u64;
+    format!(
+        "{}:{:02}:{:02}.{:02}",
+        centis / 360_000,
+        (centis / 6_000) % 60,
+        (centis / 100) % 60,
+        centis % 100
+    )
+}
+
+// Convert a color to ASS &HAABBGGRR notation, falling back to white
+fn ass_color(color: &str) -> String {
+    let (r, g, b) = color_to_rgb(color).unwrap_or((255, 255, 255));
+    format!("&H00{:02X}{:02X}{:02X}", b, g, r)
+}
+
+// Braces start override blocks in ASS, so escape them. libass has no escape for
+// a backslash, so a word joiner after it keeps \N, \n and \h from being read as codes
+fn escape_ass_text(word: &str) -> String {
+    word.replace('\\', "\\\u{2060}")
+        .replace('{', "\\{")
+        .replace('}', "\\}")
+}
+
+// Build an ASS subtitle script with one dialogue event per word.
+// The word is centered on the focus mark (2/5 of the width), or placed by
+// --align, since ASS can't express drawtext's text_w-relative x position.
+pub fn build_subtitles(
+    timings: &[(f64, f64, String)],
+    args: &crate::Args,
+    font_location: &str,
+    resolution: &Resolution,
+) -> String {
+    // libass matches fonts by family name; the file stem is the best guess we have
+    let font_name = Path::new(font_location)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let mut script = format!(
+        "[Script Info]\n\
+         ScriptType: v4.00+\n\
+         PlayResX: {width}\n\
+         PlayResY: {height}\n\
+         WrapStyle: 2\n\
+         \n\
+         [V4+ Styles]\n\
+         Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n\
+         Style: Default,{font},{size},{color},{color},&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,0,0,5,0,0,0,1\n\
+         \n\
+         [Events]\n\
+         Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n",
+        width = resolution.width,
+        height = resolution.height,
+        font = font_name,
+        size = args.fontsize,
+        color = ass_color(&args.text_color),
+    );
+
+    let (anchor, x) = args.align.ass_position(resolution.width);
+    let y = resolution.height / 2;
+
+    for (start_time, end_time, word) in timings {
+        // Same spacing and sizing as the drawtext path; the style already has the regular size
+        let lines = wrap_chunk(
+            &space_letters(word, args.letter_spacing),
+            args,
+            resolution.width,
+        );
+        let fontsize = lines
+            .iter()
+            .map(|line| word_fontsize(line, args, resolution.width))
+            .min()
+            .unwrap_or(args.fontsize);
+        let mut overrides = if fontsize != args.fontsize {
+            format!("\\fs{}", fontsize)
+        } else {
+            String::new()
+        };
+        let color = word_color(word, args);
+        if color != args.text_color {
+            let _ = write!(overrides, "\\1c{}&", ass_color(color));
+        }
+
+        let _ = writeln!(
+            script,
+            "Dialogue: 0,{},{},Default,,0,0,0,,{{\\an{}\\pos({},{}){}}}{}",
+            format_timestamp(*start_time),
+            format_timestamp(*end_time),
+            anchor,
+            x,
+            y,
+            overrides,
+            lines
+                .iter()
+                .map(|line| escape_ass_text(line))
+                .collect::<Vec<_>>()
+                .join("\\N")
+        );
+    }
+
+    script
+}
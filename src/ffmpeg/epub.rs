@@ -0,0 +1,185 @@
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+
+use crate::Error;
+
+// Read one chapter (1-based, in reading order) of an EPUB as plain text
+pub fn read_chapter(path: &Path, chapter: usize) -> Result<String> {
+    if !path.exists() {
+        bail!("EPUB file not found: {}", path.display());
+    }
+
+    let file =
+        std::fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .with_context(|| format!("{} is not an EPUB (zip) file", path.display()))?;
+
+    let chapters = spine(&mut archive)
+        .with_context(|| format!("Failed to read the contents of {}", path.display()))?;
+    if chapter == 0 || chapter > chapters.len() {
+        bail!(
+            "Chapter {} doesn't exist. {} has chapters 1 to {}",
+            chapter,
+            path.display(),
+            chapters.len()
+        );
+    }
+
+    let href = &chapters[chapter - 1];
+    let text = html_to_text(&read_entry(&mut archive, href)?);
+    if text.is_empty() {
+        return Err(Error::EmptyInput(format!("chapter {} of {}", chapter, path.display())).into());
+    }
+    Ok(text)
+}
+
+fn read_entry<R: std::io::Read + std::io::Seek>(
+    archive: &mut zip::ZipArchive<R>,
+    name: &str,
+) -> Result<String> {
+    let mut entry = archive
+        .by_name(name)
+        .with_context(|| format!("Missing {} in the EPUB", name))?;
+    let mut content = String::new();
+    entry
+        .read_to_string(&mut content)
+        .with_context(|| format!("Failed to read {} from the EPUB", name))?;
+    Ok(content)
+}
+
+// Chapter documents in reading order, as paths inside the archive.
+// META-INF/container.xml points at the package document, whose spine lists
+// manifest ids in order
+fn spine<R: std::io::Read + std::io::Seek>(
+    archive: &mut zip::ZipArchive<R>,
+) -> Result<Vec<String>> {
+    let container = read_entry(archive, "META-INF/container.xml")?;
+    let container = roxmltree::Document::parse(&container)?;
+    let package_path = container
+        .descendants()
+        .find(|node| node.has_tag_name("rootfile"))
+        .and_then(|node| node.attribute("full-path"))
+        .context("container.xml has no rootfile")?
+        .to_string();
+
+    let package = read_entry(archive, &package_path)?;
+    let package = roxmltree::Document::parse(&package)?;
+    // Manifest hrefs are relative to the package document
+    let base = package_path
+        .rfind('/')
+        .map_or("", |slash| &package_path[..=slash]);
+
+    let hrefs = package
+        .descendants()
+        .filter(|node| node.has_tag_name("itemref"))
+        .filter_map(|itemref| {
+            let id = itemref.attribute("idref")?;
+            package
+                .descendants()
+                .find(|item| item.has_tag_name("item") && item.attribute("id") == Some(id))
+                .and_then(|item| item.attribute("href"))
+        })
+        .map(|href| format!("{}{}", base, percent_decode(href)))
+        .collect();
+    Ok(hrefs)
+}
+
+// Hrefs are URLs, so names with spaces or non-ASCII letters arrive escaped
+fn percent_decode(href: &str) -> String {
+    let bytes = href.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(byte) = href
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// Tags whose end starts a new paragraph, so the paragraph pause applies
+const BLOCK_TAGS: &[&str] = &[
+    "p",
+    "div",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "li",
+    "blockquote",
+    "section",
+    "tr",
+];
+
+// Strip markup from an XHTML chapter, keeping paragraphs apart with blank lines.
+// Chapters are often not well-formed XML (HTML entities, stray tags), so this
+// scans tags instead of parsing
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    let mut skip_until: Option<&str> = None;
+
+    while let Some(open) = rest.find('<') {
+        if skip_until.is_none() {
+            text.push_str(&rest[..open]);
+        }
+        let Some(close) = rest[open..].find('>') else {
+            rest = "";
+            break;
+        };
+        let tag = &rest[open + 1..open + close];
+        rest = &rest[open + close + 1..];
+
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+
+        // Nothing in the head, scripts or styles is read aloud
+        if let Some(end) = skip_until {
+            if tag.starts_with('/') && name == end {
+                skip_until = None;
+            }
+            continue;
+        }
+        if !tag.starts_with('/') && !tag.ends_with('/') {
+            skip_until = match name.as_str() {
+                "head" => Some("head"),
+                "script" => Some("script"),
+                "style" => Some("style"),
+                _ => None,
+            };
+        }
+
+        if name == "br" {
+            text.push('\n');
+        } else if BLOCK_TAGS.contains(&name.as_str()) {
+            text.push_str("\n\n");
+        }
+    }
+    if skip_until.is_none() {
+        text.push_str(rest);
+    }
+
+    let text = html_escape::decode_html_entities(&text);
+    // Collapse the source indentation, keeping blank lines between paragraphs
+    text.split("\n\n")
+        .map(|paragraph| paragraph.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|paragraph| !paragraph.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
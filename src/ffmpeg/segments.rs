@@ -0,0 +1,211 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+
+use super::{
+    Bgm, Container, Resolution, add_audio_inputs, add_audio_output, ass, build_ffmpeg_command,
+    build_filters, format_command, partial_path, tts,
+};
+
+// A contiguous slice of the video with its timings shifted to start at 0s
+pub struct Segment {
+    pub start: f64,
+    pub end: f64,
+    pub timings: Vec<(f64, f64, String)>,
+}
+
+// Cut the timings into at most `jobs` slices of similar length. Cuts fall on word
+// starts, after the countdown, so no word is split between two files
+pub fn split_segments(
+    timings: &[(f64, f64, String)],
+    countdown: usize,
+    total_duration: f64,
+    jobs: usize,
+) -> Vec<Segment> {
+    let mut cuts = vec![0];
+    for k in 1..jobs {
+        let target = total_duration * k as f64 / jobs as f64;
+        if let Some(i) = timings.iter().position(|(start, _, _)| *start >= target) {
+            let i = i.max(countdown);
+            if i > *cuts.last().unwrap_or(&0) && i < timings.len() {
+                cuts.push(i);
+            }
+        }
+    }
+
+    cuts.iter()
+        .enumerate()
+        .map(|(n, &cut)| {
+            let next = cuts.get(n + 1).copied();
+            let start = if n == 0 { 0.0 } else { timings[cut].0 };
+            let end = next.map_or(total_duration, |next| timings[next].0);
+            let timings = timings[cut..next.unwrap_or(timings.len())]
+                .iter()
+                .map(|(word_start, word_end, word)| {
+                    (word_start - start, word_end - start, word.clone())
+                })
+                .collect();
+            Segment {
+                start,
+                end,
+                timings,
+            }
+        })
+        .collect()
+}
+
+// Render the video-only segments side by side, returning the files in order.
+// Audio is left to the concat step so it runs continuously across the cuts
+pub fn render(
+    args: &crate::Args,
+    segments: &[Segment],
+    font_location: &str,
+    resolution: &Resolution,
+    container: Container,
+    use_ass: bool,
+) -> Result<Vec<String>> {
+    let mut commands = Vec::with_capacity(segments.len());
+    let mut subtitle_files = Vec::new();
+    let mut parts = Vec::with_capacity(segments.len());
+
+    for (i, segment) in segments.iter().enumerate() {
+        let mut segment_args = args.clone();
+        segment_args.output = std::env::temp_dir()
+            .join(format!("src-cli-{}-segment{}", std::process::id(), i))
+            .to_string_lossy()
+            .to_string();
+        // The countdown is all in the first segment and the outro in the last
+        if i > 0 {
+            segment_args.countdown = 0;
+        }
+        if i + 1 < segments.len() {
+            segment_args.outro_text = None;
+        }
+
+        let subtitle_file = if use_ass {
+            let path = PathBuf::from(format!("{}.ass", segment_args.output));
+            let script =
+                ass::build_subtitles(&segment.timings, &segment_args, font_location, resolution);
+            if let Err(err) = std::fs::write(&path, script) {
+                remove_parts(&subtitle_files);
+                return Err(err)
+                    .with_context(|| format!("Failed to write subtitles to {}", path.display()));
+            }
+            subtitle_files.push(path.clone());
+            Some(path)
+        } else {
+            None
+        };
+
+        let filter_chain = build_filters(
+            &segment.timings,
+            &segment_args,
+            font_location,
+            resolution,
+            subtitle_file.as_deref(),
+        )
+        .join(",");
+
+        commands.push(build_ffmpeg_command(
+            &segment_args,
+            &[],
+            None,
+            &filter_chain,
+            segment.end - segment.start,
+            resolution,
+            container,
+        ));
+        parts.push(partial_path(&segment_args.output));
+    }
+
+    // Print the commands instead of running them (subtitle files are kept for reuse)
+    if args.dry_run {
+        for cmd in &commands {
+            println!("{}", format_command(cmd));
+        }
+        return Ok(parts);
+    }
+
+    info!("Rendering {} segments in parallel...", commands.len());
+    let outputs: Vec<_> = std::thread::scope(|scope| {
+        let handles: Vec<_> = commands
+            .iter_mut()
+            .map(|cmd| {
+                debug!("Running: {}", format_command(cmd));
+                scope.spawn(move || cmd.stdin(Stdio::null()).output())
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("segment thread panicked"))
+            .collect()
+    });
+
+    remove_parts(&subtitle_files);
+
+    for output in outputs {
+        let output = match output {
+            Ok(output) if output.status.success() => continue,
+            Ok(output) => output,
+            Err(err) => {
+                remove_parts(&parts);
+                return Err(err).context("Failed to execute ffmpeg. Is it installed?");
+            }
+        };
+        remove_parts(&parts);
+        bail!(
+            "FFmpeg failed on a segment:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    Ok(parts)
+}
+
+pub fn remove_parts<P: AsRef<Path>>(parts: &[P]) {
+    for part in parts {
+        let _ = std::fs::remove_file(part);
+    }
+}
+
+// Concat demuxer list, quoting each path for its single-quoted syntax
+pub fn concat_list(parts: &[String]) -> String {
+    parts
+        .iter()
+        .map(|part| format!("file '{}'\n", part.replace('\'', "'\\''")))
+        .collect()
+}
+
+// Join the segments without re-encoding the video, mixing in the audio over the whole length
+pub fn concat_command(
+    args: &crate::Args,
+    list_path: &Path,
+    bgm_locations: &[Bgm],
+    narration: Option<&tts::Narration>,
+    total_duration: f64,
+    container: Container,
+) -> Command {
+    let mut cmd = Command::new("ffmpeg");
+    cmd.args([
+        "-hide_banner",
+        "-loglevel",
+        "error",
+        "-progress",
+        "pipe:1",
+        "-nostats",
+        "-f",
+        "concat",
+        "-safe",
+        "0",
+        "-i",
+    ]);
+    cmd.arg(list_path);
+    add_audio_inputs(&mut cmd, args, bgm_locations, narration);
+
+    cmd.args(["-map", "0:v:0"]);
+    add_audio_output(&mut cmd, args, bgm_locations, narration, total_duration);
+    cmd.args(["-c:v", "copy", "-y", "-f", container.muxer()]);
+    cmd.arg(partial_path(&args.output));
+    cmd
+}
@@ -0,0 +1,31 @@
+use std::fmt::Write;
+
+// Format seconds as an SRT timestamp (HH:MM:SS,mmm)
+fn format_timestamp(seconds: f64) -> String {
+    let millis = (seconds * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        millis / 3_600_000,
+        (millis / 60_000) % 60,
+        (millis / 1000) % 60,
+        millis % 1000
+    )
+}
+
+// Build an SRT document with one cue per word
+pub fn build_srt(timings: &[(f64, f64, String)]) -> String {
+    let mut srt = String::new();
+
+    for (i, (start_time, end_time, word)) in timings.iter().enumerate() {
+        let _ = writeln!(
+            srt,
+            "{}\n{} --> {}\n{}\n",
+            i + 1,
+            format_timestamp(*start_time),
+            format_timestamp(*end_time),
+            word
+        );
+    }
+
+    srt
+}
@@ -1,31 +1,292 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
 use jieba_rs::Jieba;
-use once_cell::sync::Lazy;
 use unicode_segmentation::UnicodeSegmentation;
 
-// We use Lazy to ensure the dictionary is only loaded once into memory,
-// making the function much faster for repeated calls.
-static JIEBA: Lazy<Jieba> = Lazy::new(Jieba::new);
+/// Build the Jieba instance once per run, adding user words from a dictionary file.
+/// Each line is "word [freq] [tag]"; without a frequency Jieba suggests one that keeps the word whole.
+pub fn load_jieba(user_dict: Option<&Path>) -> Result<Jieba> {
+    let mut jieba = Jieba::new();
+
+    if let Some(path) = user_dict {
+        let dict = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read Jieba dictionary {}", path.display()))?;
+
+        for (line_no, line) in dict.lines().enumerate() {
+            let mut fields = line.split_whitespace();
+            let Some(word) = fields.next() else {
+                continue;
+            };
+            let freq = fields
+                .next()
+                .map(|freq| {
+                    freq.parse::<usize>().with_context(|| {
+                        format!(
+                            "Invalid frequency '{}' on line {} of {}",
+                            freq,
+                            line_no + 1,
+                            path.display()
+                        )
+                    })
+                })
+                .transpose()?;
+            jieba.add_word(word, freq, fields.next());
+        }
+    }
+
+    Ok(jieba)
+}
+
+/// A segmented word and which pause, if any, follows it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub text: String,
+    pub ends_sentence: bool,
+    /// Ends with a comma-like mark (, ; : and their CJK forms)
+    pub ends_clause: bool,
+    /// Last word before a blank line and another paragraph
+    pub ends_paragraph: bool,
+}
+
+/// Whether the text has Chinese or Japanese characters that need Jieba.
+/// Callers can skip loading the dictionary when this is false.
+pub fn has_cjk(text: &str) -> bool {
+    text.chars().any(|c| {
+        ('\u{4e00}'..='\u{9fff}').contains(&c) || // Chinese
+        ('\u{3040}'..='\u{30ff}').contains(&c) // Japanese
+    })
+}
+
+/// Split text into tokens, marking pauses once here so the timing loop doesn't re-detect them.
+/// Without Jieba, CJK runs fall back to Unicode word bounds.
+pub fn split_text(text: &str, jieba: Option<&Jieba>, abbreviations: &[String]) -> Vec<Token> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let text = remove_invisible(text);
+
+    for paragraph in split_paragraphs(&text) {
+        // A following paragraph turns the previous word into a paragraph end
+        if let Some(last) = tokens.last_mut() {
+            last.ends_paragraph = true;
+        }
+
+        tokens.extend(split_words(&paragraph, jieba).into_iter().map(|text| {
+            let ends_sentence = is_sentence_end(&text, abbreviations);
+            Token {
+                ends_clause: !ends_sentence
+                    && text.ends_with([',', ';', ':', '，', '、', '；', '：']),
+                ends_sentence,
+                ends_paragraph: false,
+                text,
+            }
+        }));
+    }
+
+    tokens
+}
+
+// Drop characters that render as nothing: BOMs, zero-width spaces, soft hyphens
+// and control codes. Joiners (U+200C/U+200D) stay, since emoji sequences and
+// Indic scripts need them
+fn remove_invisible(text: &str) -> String {
+    text.chars()
+        .filter(|&c| {
+            !matches!(
+                c,
+                '\u{00AD}' | '\u{180E}' | '\u{200B}' | '\u{2060}'..='\u{2064}' | '\u{FEFF}'
+            ) && (!c.is_control() || c.is_whitespace())
+        })
+        .collect()
+}
+
+// Group lines into paragraphs separated by blank (or whitespace-only) lines
+fn split_paragraphs(text: &str) -> Vec<String> {
+    let mut paragraphs = Vec::new();
+    let mut current = String::new();
+
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            if !current.is_empty() {
+                paragraphs.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+
+        if !current.is_empty() {
+            current.push('\n');
+        }
+        current.push_str(line);
+    }
+
+    if !current.is_empty() {
+        paragraphs.push(current);
+    }
+
+    paragraphs
+}
+
+/// Remove Markdown syntax so only the prose is shown: heading, quote and list
+/// markers, code fences, horizontal rules, emphasis and code markers, and link
+/// or image targets (`[text](url)` becomes `text`). Code block contents are kept.
+pub fn strip_markdown(text: &str) -> String {
+    let mut lines = Vec::new();
+    let mut in_fence = false;
+
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            lines.push(String::new());
+            continue;
+        }
+        if in_fence {
+            lines.push(line.to_string());
+            continue;
+        }
+        if is_horizontal_rule(trimmed) {
+            lines.push(String::new());
+            continue;
+        }
+        lines.push(strip_inline_markdown(&strip_line_markers(trimmed)));
+    }
+
+    lines.join("\n")
+}
+
+// "---", "***" or "___", optionally spaced out
+fn is_horizontal_rule(line: &str) -> bool {
+    let marks: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+    marks.len() >= 3
+        && ['-', '*', '_']
+            .iter()
+            .any(|&mark| marks.chars().all(|c| c == mark))
+}
+
+// Drop block prefixes: quotes, then a heading or list marker
+fn strip_line_markers(line: &str) -> String {
+    let mut line = line;
+    while let Some(rest) = line.strip_prefix('>') {
+        line = rest.trim_start();
+    }
+
+    let hashes = line.chars().take_while(|&c| c == '#').count();
+    if (1..=6).contains(&hashes) && line[hashes..].starts_with(' ') {
+        return line[hashes..]
+            .trim()
+            .trim_end_matches('#')
+            .trim_end()
+            .to_string();
+    }
+
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(rest) = line.strip_prefix(bullet) {
+            return rest.to_string();
+        }
+    }
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    if digits > 0
+        && let Some(rest) = line[digits..]
+            .strip_prefix(". ")
+            .or_else(|| line[digits..].strip_prefix(") "))
+    {
+        return rest.to_string();
+    }
+
+    line.to_string()
+}
+
+// Replace links and images with their text, then drop emphasis and code markers
+// that aren't inside a word, so snake_case and 2*3 survive
+fn strip_inline_markdown(line: &str) -> String {
+    let mut linked = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(open) = rest.find('[') {
+        let Some(close) = rest[open..].find(']').map(|i| open + i) else {
+            break;
+        };
+        let target_end = rest[close + 1..]
+            .starts_with('(')
+            .then(|| rest[close..].find(')').map(|i| close + i))
+            .flatten();
+        let Some(target_end) = target_end else {
+            // Plain brackets, not a link
+            linked.push_str(&rest[..=close]);
+            rest = &rest[close + 1..];
+            continue;
+        };
+        let before = &rest[..open];
+        linked.push_str(before.strip_suffix('!').unwrap_or(before));
+        linked.push_str(&rest[open + 1..close]);
+        rest = &rest[target_end + 1..];
+    }
+    linked.push_str(rest);
+
+    let chars: Vec<char> = linked.chars().collect();
+    let is_marker = |c: char| matches!(c, '*' | '_' | '`' | '~');
+    let mut stripped = String::with_capacity(linked.len());
+    let mut i = 0;
+    while i < chars.len() {
+        if !is_marker(chars[i]) {
+            stripped.push(chars[i]);
+            i += 1;
+            continue;
+        }
+        let run_end = (i..chars.len())
+            .find(|&j| !is_marker(chars[j]))
+            .unwrap_or(chars.len());
+        let inside_word = i > 0
+            && chars[i - 1].is_alphanumeric()
+            && chars.get(run_end).is_some_and(|c| c.is_alphanumeric());
+        if inside_word {
+            stripped.extend(&chars[i..run_end]);
+        }
+        i = run_end;
+    }
+
+    stripped
+}
 
-pub fn split_text(text: &str) -> Vec<String> {
+fn split_words(text: &str, jieba: Option<&Jieba>) -> Vec<String> {
     let mut words: Vec<String> = Vec::new();
     let mut current_segment = String::new();
     let mut in_quotes = false;
-    let mut chars = text.chars().peekable();
+    // A quoted foreign phrase in CJK text is read as one unit, like a name
+    let keep_quoted = has_cjk(text);
+    let mut chars = text.char_indices().peekable();
 
-    while let Some(c) = chars.next() {
+    while let Some((i, c)) = chars.next() {
         match c {
+            '"' if keep_quoted
+                && !in_quotes
+                && let Some(quoted) = quoted_phrase(&text[i..]) =>
+            {
+                if !current_segment.is_empty() {
+                    words.extend(process_segment(&current_segment, jieba));
+                    current_segment.clear();
+                }
+
+                // Punctuation after the closing quote stays with the phrase
+                let rest = &text[i + quoted.len()..];
+                let end = i
+                    + quoted.len()
+                    + rest
+                        .find(|c: char| !is_trailing_punctuation(&c.to_string()))
+                        .unwrap_or(rest.len());
+                words.push(text[i..end].to_string());
+                while chars.next_if(|(j, _)| *j < end).is_some() {}
+            }
             // Handle quotes but protect English contractions like "it's"
             '\'' | '"' => {
                 let is_contraction = c == '\''
                     && !current_segment.is_empty()
-                    && chars.peek().is_some_and(|next| next.is_alphabetic());
+                    && chars.peek().is_some_and(|(_, next)| next.is_alphabetic());
 
                 if is_contraction {
                     current_segment.push(c);
                 } else {
                     // It's a quote boundary
                     if !current_segment.is_empty() {
-                        words.extend(process_segment(&current_segment));
+                        words.extend(process_segment(&current_segment, jieba));
                         current_segment.clear();
                     }
                     in_quotes = !in_quotes;
@@ -34,7 +295,7 @@ pub fn split_text(text: &str) -> Vec<String> {
             // Split by whitespace only if not in a quote
             c if c.is_whitespace() && !in_quotes => {
                 if !current_segment.is_empty() {
-                    words.extend(process_segment(&current_segment));
+                    words.extend(process_segment(&current_segment, jieba));
                     current_segment.clear();
                 }
             }
@@ -45,26 +306,57 @@ pub fn split_text(text: &str) -> Vec<String> {
     }
 
     if !current_segment.is_empty() {
-        words.extend(process_segment(&current_segment));
+        words.extend(process_segment(&current_segment, jieba));
     }
     words
 }
 
-fn process_segment(segment: &str) -> Vec<String> {
+// The double-quoted phrase at the start of `text`, quotes included, when it
+// closes on the same line and has no CJK of its own to segment
+fn quoted_phrase(text: &str) -> Option<&str> {
+    let inner_len = text[1..].find(['"', '\n'])?;
+    let inner = &text[1..1 + inner_len];
+
+    if text[1 + inner_len..].starts_with('"') && !inner.trim().is_empty() && !has_cjk(inner) {
+        Some(&text[..inner_len + 2])
+    } else {
+        None
+    }
+}
+
+fn process_segment(segment: &str, jieba: Option<&Jieba>) -> Vec<String> {
+    // Links and addresses would be shattered on ':', '/', '.' and '@'
+    if is_url_or_email(segment) {
+        return vec![segment.to_string()];
+    }
+
+    // Jieba is tuned for Chinese and cuts kana one character at a time
+    if is_japanese(segment) {
+        return split_japanese(segment);
+    }
+
     let mut result: Vec<String> = Vec::new();
-    let has_cjk = segment.chars().any(|c| {
-        ('\u{4e00}'..='\u{9fff}').contains(&c) || // Chinese
-        ('\u{3040}'..='\u{30ff}').contains(&c) // Japanese
-    });
 
-    if has_cjk {
+    if let Some(jieba) = jieba.filter(|_| has_cjk(segment)) {
         // Jieba logic (Existing)
-        JIEBA
+        let tokens = jieba
             .cut(segment, true)
             .into_iter()
             .map(|s| s.to_string())
             .filter(|s| !s.trim().is_empty())
-            .collect()
+            .collect();
+        // Jieba splits "1,000" into "1", ",", "000"; Unicode word bounds already keep them
+        let tokens = merge_numbers(tokens);
+
+        // Jieba emits punctuation as separate tokens; reattach it so "好。" shows
+        // together and the sentence pause follows the word
+        for token in tokens {
+            match result.last_mut() {
+                Some(last_word) if is_trailing_punctuation(&token) => last_word.push_str(&token),
+                _ => result.push(token),
+            }
+        }
+        result
     } else {
         // split_word_bounds() gives us words, punctuation, and spaces as separate tokens
         let mut tokens = segment.split_word_bounds().peekable();
@@ -75,9 +367,7 @@ fn process_segment(segment: &str) -> Vec<String> {
             }
 
             // 2. Identify if this token is a punctuation mark we want to merge
-            let is_punctuation = matches!(token, "." | "," | "!" | "?" | "。" | "、" | "！" | "？");
-
-            if is_punctuation && !result.is_empty() {
+            if is_trailing_punctuation(token) && !result.is_empty() {
                 // Reattach to the previous word
                 if let Some(last_word) = result.last_mut() {
                     last_word.push_str(token);
@@ -87,20 +377,27 @@ fn process_segment(segment: &str) -> Vec<String> {
 
             // 3. Identify if this is a hyphen connector (for world-test)
             if token == "-"
-                && !result.is_empty()
-                && let Some(next_token) = tokens.peek()
+                && let Some(last_word) = result.last_mut()
             {
-                // If the next part is a word, merge [prev] + [-] + [next]
-                if !next_token.trim().is_empty() {
-                    let mut hyphenated = result.pop().unwrap();
-                    hyphenated.push('-');
-                    hyphenated.push_str(tokens.next().unwrap()); // Consume the word after hyphen
-                    result.push(hyphenated);
-                    continue;
+                last_word.push('-');
+
+                // If the next part is a word, merge [prev] + [-] + [next]; otherwise
+                // keep the trailing hyphen ("co-" before a line break) on the word
+                if let Some(next_token) =
+                    tokens.next_if(|next| next.chars().any(char::is_alphanumeric))
+                {
+                    last_word.push_str(next_token);
                 }
+                continue;
             }
 
-            // 4. Only add if it contains alphanumeric characters (ignores lone symbols)
+            // 4. Hangul runs without spaces are split into readable pieces
+            if token.chars().any(is_hangul) {
+                result.extend(split_hangul(token));
+                continue;
+            }
+
+            // 5. Only add if it contains alphanumeric characters (ignores lone symbols)
             if token.chars().any(|c| c.is_alphanumeric()) {
                 result.push(token.to_string());
             }
@@ -109,46 +406,449 @@ fn process_segment(segment: &str) -> Vec<String> {
     }
 }
 
+// Detect URL-like and email-like segments, ignoring surrounding punctuation
+fn is_url_or_email(segment: &str) -> bool {
+    let core = segment
+        .trim_start_matches(['(', '[', '<'])
+        .trim_end_matches(['.', ',', '!', '?', ';', ')', ']', '>']);
+    let lower = core.to_lowercase();
+
+    if ["http://", "https://", "ftp://", "www."]
+        .iter()
+        .any(|prefix| lower.starts_with(prefix) && lower.len() > prefix.len())
+    {
+        return true;
+    }
+
+    match core.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && !domain.contains('@')
+                && domain
+                    .split_once('.')
+                    .is_some_and(|(host, tld)| !host.is_empty() && !tld.is_empty())
+        }
+        None => false,
+    }
+}
+
+// Split a word into (left, pivot, right) around its optimal recognition point,
+// roughly 30% into the word, ignoring trailing punctuation
+pub fn split_at_orp(word: &str) -> (&str, &str, &str) {
+    let letters = word
+        .trim_end_matches(|c: char| !c.is_alphanumeric())
+        .chars()
+        .count()
+        .max(1);
+    let pivot_index = (letters * 3 / 10).min(letters - 1);
+
+    let mut indices = word.char_indices().map(|(i, _)| i).skip(pivot_index);
+    let Some(pivot_start) = indices.next() else {
+        return (word, "", "");
+    };
+    let pivot_end = indices.next().unwrap_or(word.len());
+
+    (
+        &word[..pivot_start],
+        &word[pivot_start..pivot_end],
+        &word[pivot_end..],
+    )
+}
+
+// Common abbreviations that end with a dot but don't end a sentence
+const ABBREVIATIONS: &[&str] = &[
+    "mr", "mrs", "ms", "dr", "prof", "sr", "jr", "st", "vs", "inc", "ltd", "fig", "mt", "e.g",
+    "i.e", "cf", "approx",
+];
+
+// Check whether a word ends a sentence, skipping abbreviations like "Mr." and "U.S."
+fn is_sentence_end(word: &str, extra_abbreviations: &[String]) -> bool {
+    if word.ends_with(['!', '?', '。', '！', '？']) {
+        return true;
+    }
+
+    let Some(stem) = word.strip_suffix('.') else {
+        return false;
+    };
+    let stem = stem
+        .trim_start_matches(&['(', '[', '{', '"', '\''][..])
+        .to_lowercase();
+
+    let is_known = ABBREVIATIONS.contains(&stem.as_str())
+        || extra_abbreviations
+            .iter()
+            .any(|abbr| abbr.trim_end_matches('.').to_lowercase() == stem);
+
+    // Single letters joined by dots (U.S., e.g., J.)
+    let is_initialism = stem
+        .split('.')
+        .all(|part| part.chars().count() == 1 && part.chars().all(char::is_alphabetic));
+
+    !(is_known || is_initialism)
+}
+
+// Punctuation that belongs to the end of the previous word
+fn is_trailing_punctuation(token: &str) -> bool {
+    matches!(
+        token,
+        "." | "," | "!" | "?" | "。" | "、" | "，" | "！" | "？" | "；" | "："
+    )
+}
+
+// Rejoin digit-separator-digit sequences like "3.14" and "1,000,000"
+fn merge_numbers(tokens: Vec<String>) -> Vec<String> {
+    let mut result: Vec<String> = Vec::with_capacity(tokens.len());
+    let mut tokens = tokens.into_iter().peekable();
+
+    while let Some(token) = tokens.next() {
+        let is_separator = token == "." || token == ",";
+        let follows_digit = result
+            .last()
+            .is_some_and(|prev| prev.ends_with(|c: char| c.is_ascii_digit()));
+        let precedes_digit = tokens
+            .peek()
+            .is_some_and(|next| next.starts_with(|c: char| c.is_ascii_digit()));
+
+        if is_separator
+            && follows_digit
+            && precedes_digit
+            && let Some(last) = result.last_mut()
+            && let Some(next) = tokens.next()
+        {
+            last.push_str(&token);
+            last.push_str(&next);
+            continue;
+        }
+
+        result.push(token);
+    }
+
+    result
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Script {
+    Kanji,
+    Hiragana,
+    Katakana,
+    Latin,
+    Other,
+}
+
+fn script(c: char) -> Script {
+    match c {
+        '\u{4e00}'..='\u{9fff}' | '\u{3400}'..='\u{4dbf}' | '々' => Script::Kanji,
+        '\u{3040}'..='\u{309f}' => Script::Hiragana,
+        // Includes the prolonged sound mark ー
+        '\u{30a0}'..='\u{30ff}' => Script::Katakana,
+        c if c.is_alphanumeric() => Script::Latin,
+        _ => Script::Other,
+    }
+}
+
+// Kana only appears in Japanese, so any kana selects the Japanese splitter over Jieba
+fn is_japanese(segment: &str) -> bool {
+    segment
+        .chars()
+        .any(|c| matches!(script(c), Script::Hiragana | Script::Katakana))
+}
+
+// Hiragana that usually starts a particle ("は", "が", "では") rather than a verb ending
+const PARTICLE_STARTS: &[char] = &[
+    'は', 'が', 'を', 'に', 'で', 'と', 'の', 'も', 'へ', 'や', 'か', 'ね', 'よ',
+];
+
+// Split Japanese on script boundaries: kanji keep their kana endings ("食べました"),
+// particles and katakana words stand alone, and punctuation joins its neighbor
+fn split_japanese(segment: &str) -> Vec<String> {
+    let mut runs: Vec<String> = Vec::new();
+    for c in segment.chars() {
+        match runs.last_mut() {
+            Some(run)
+                if script(c) != Script::Other
+                    && run
+                        .chars()
+                        .last()
+                        .is_some_and(|last| script(last) == script(c)) =>
+            {
+                run.push(c)
+            }
+            _ => runs.push(c.to_string()),
+        }
+    }
+
+    let mut result: Vec<String> = Vec::new();
+    let mut prefix = String::new();
+    let mut previous = Script::Other;
+
+    for run in merge_numbers(runs) {
+        let current = run.chars().next().map_or(Script::Other, script);
+
+        match current {
+            _ if run.trim().is_empty() => {}
+            // Opening brackets go with the next word, everything else with the last
+            Script::Other if run.starts_with(['「', '『', '（', '(', '【', '〈', '《']) => {
+                prefix.push_str(&run)
+            }
+            Script::Other => match result.last_mut() {
+                Some(last) => last.push_str(&run),
+                None => prefix.push_str(&run),
+            },
+            Script::Hiragana
+                if previous == Script::Kanji
+                    && prefix.is_empty()
+                    && !run.starts_with(PARTICLE_STARTS) =>
+            {
+                if let Some(last) = result.last_mut() {
+                    last.push_str(&run);
+                }
+            }
+            _ => result.push(std::mem::take(&mut prefix) + &run),
+        }
+        previous = current;
+    }
+
+    if !prefix.is_empty() {
+        match result.last_mut() {
+            Some(last) => last.push_str(&prefix),
+            None => result.push(prefix),
+        }
+    }
+
+    result
+}
+
+// Longest Hangul run kept as a single word
+const MAX_HANGUL_SYLLABLES: usize = 5;
+
+/// Whether a word is written right to left (Hebrew, Arabic, Syriac, Thaana, N'Ko)
+pub fn is_rtl(word: &str) -> bool {
+    word.chars().any(|c| {
+        ('\u{0590}'..='\u{08ff}').contains(&c) // Hebrew through Arabic Extended-A
+            || ('\u{fb1d}'..='\u{fdff}').contains(&c) // Hebrew and Arabic presentation forms A
+            || ('\u{fe70}'..='\u{feff}').contains(&c) // Arabic presentation forms B
+    })
+}
+
+fn is_hangul(c: char) -> bool {
+    ('\u{AC00}'..='\u{D7A3}').contains(&c)
+}
+
+// Split a long Hangul run into evenly sized pieces of at most MAX_HANGUL_SYLLABLES
+fn split_hangul(token: &str) -> Vec<String> {
+    let chars: Vec<char> = token.chars().collect();
+    if chars.len() <= MAX_HANGUL_SYLLABLES {
+        return vec![token.to_string()];
+    }
+
+    let pieces = chars.len().div_ceil(MAX_HANGUL_SYLLABLES);
+    let piece_len = chars.len().div_ceil(pieces);
+    chars
+        .chunks(piece_len)
+        .map(|chunk| chunk.iter().collect())
+        .collect()
+}
+
+/// Hard-split tokens longer than `max_chars` characters into evenly sized pieces.
+/// The pauses that followed the token follow its last piece.
+pub fn split_long_words(tokens: Vec<Token>, max_chars: usize) -> Vec<Token> {
+    let mut result = Vec::with_capacity(tokens.len());
+
+    for token in tokens {
+        let graphemes: Vec<&str> = token.text.graphemes(true).collect();
+        if graphemes.len() <= max_chars {
+            result.push(token);
+            continue;
+        }
+
+        let pieces = graphemes.len().div_ceil(max_chars);
+        let piece_len = graphemes.len().div_ceil(pieces);
+        let mut chunks = graphemes.chunks(piece_len).peekable();
+        while let Some(chunk) = chunks.next() {
+            let last = chunks.peek().is_none();
+            result.push(Token {
+                text: chunk.concat(),
+                ends_sentence: last && token.ends_sentence,
+                ends_clause: last && token.ends_clause,
+                ends_paragraph: last && token.ends_paragraph,
+            });
+        }
+    }
+
+    result
+}
+
 // Strip trailing punctuation and quotes from a word for dictionary lookup
-#[allow(dead_code)]
 pub fn clean_word_for_lookup(word: &str) -> String {
     word.trim_end_matches(&[',', '.', '?', '!', ';', ':', '"', '\'', ')', ']', '}'][..])
         .trim_start_matches(&['(', '[', '{', '"', '\''][..])
         .to_string()
 }
+
+// Frequent English words that are long enough to pass the length threshold
+// but carry little meaning on their own
+const COMMON_WORDS: &[&str] = &[
+    "about",
+    "above",
+    "actually",
+    "after",
+    "again",
+    "against",
+    "almost",
+    "already",
+    "although",
+    "always",
+    "another",
+    "anything",
+    "around",
+    "because",
+    "before",
+    "being",
+    "below",
+    "between",
+    "cannot",
+    "could",
+    "different",
+    "during",
+    "either",
+    "enough",
+    "especially",
+    "every",
+    "everyone",
+    "everything",
+    "example",
+    "following",
+    "further",
+    "having",
+    "however",
+    "important",
+    "instead",
+    "itself",
+    "little",
+    "might",
+    "myself",
+    "nothing",
+    "often",
+    "other",
+    "others",
+    "otherwise",
+    "perhaps",
+    "people",
+    "probably",
+    "rather",
+    "really",
+    "should",
+    "similar",
+    "something",
+    "sometimes",
+    "still",
+    "themselves",
+    "there",
+    "therefore",
+    "these",
+    "thing",
+    "things",
+    "think",
+    "though",
+    "through",
+    "together",
+    "toward",
+    "towards",
+    "under",
+    "until",
+    "usually",
+    "whatever",
+    "whether",
+    "which",
+    "while",
+    "within",
+    "without",
+    "would",
+    "yourself",
+];
+
+// Words worth highlighting: at least `min_length` letters and, unless the
+// stoplist is off, not one of the common filler words
+pub fn is_emphasized(word: &str, min_length: usize, use_stoplist: bool) -> bool {
+    let word = clean_word_for_lookup(word).to_lowercase();
+    if word.chars().filter(|c| c.is_alphanumeric()).count() < min_length {
+        return false;
+    }
+    !(use_stoplist && COMMON_WORDS.contains(&word.as_str()))
+}
 #[cfg(test)]
 mod tests {
     use super::*;
+    use once_cell::sync::Lazy;
+
+    // Loading the dictionary is slow, so tests share one instance
+    static JIEBA: Lazy<Jieba> = Lazy::new(Jieba::new);
+
+    fn words(text: &str) -> Vec<String> {
+        split_words(text, Some(&JIEBA))
+    }
+
+    #[test]
+    fn test_japanese_segmentation_with_punctuation() {
+        let input = "上の例では、データ。";
+        let result = words(input);
 
-    // japanese is broken for JIEBA
-    // #[test]
-    // fn test_japanese_segmentation_with_punctuation() {
-    //     let input = "上の例では、データ。";
-    //     let result = split_text(input);
+        // Kana runs stay together and punctuation merges into the previous word
+        assert_eq!(result, vec!["上", "の", "例", "では、", "データ。"]);
+    }
+
+    #[test]
+    fn test_japanese_sentence() {
+        let result = words("私は東京で「ラーメン」を食べました。とても美味しかったです！");
+        assert_eq!(
+            result,
+            vec![
+                "私",
+                "は",
+                "東京",
+                "で",
+                "「ラーメン」",
+                "を",
+                "食べました。",
+                "とても",
+                "美味しかったです！"
+            ]
+        );
+
+        // Numbers keep their separators, commas join the previous word
+        assert_eq!(
+            words("価格は1,000円、安い。"),
+            vec!["価格", "は", "1,000", "円、", "安い。"]
+        );
+
+        let tokens = split_text("データです、そして終わり。", None, &[]);
+        assert!(tokens[0].text == "データ" && !tokens[0].ends_clause);
+        assert!(tokens[1].ends_clause && tokens.last().unwrap().ends_sentence);
+    }
+
+    #[test]
+    fn test_quoted_text_preservation() {
+        let input = "これは \"Special Case\" です。";
+        let result = words(input);
 
-    //     // Expected behavior:
-    //     // "上", "の", "例", "では、", "データ。"
-    //     // Note: particles like "では" often stay together in Jieba,
-    //     // and punctuation should merge.
-    //     println!("test_japanese_segmentation_with_punctuation {:?}", result);
-    //     assert_eq!(result[3], "では、");
-    //     assert_eq!(result[4], "データ。");
-    // }
+        assert_eq!(result, vec!["これは", "\"Special Case\"", "です。"]);
 
-    // #[test]
-    // fn test_quoted_text_preservation() {
-    //     let input = "これは \"Special Case\" です。";
-    //     let result = split_text(input);
+        // Punctuation after the closing quote stays with the phrase
+        let result = words("他說\"Hello World\"。然後走了");
+        assert!(result.contains(&"\"Hello World\"。".to_string()));
 
-    //     // Expected: ["これは", "\"Special Case\"", "です。"]
-    //     assert!(result.contains(&"\"Special Case\"".to_string()));
-    //     assert_eq!(result.last().unwrap(), "です。");
-    // }
+        // Quoted CJK is still segmented, and English-only text still splits quotes
+        assert!(!words("他說\"這是測試\"").iter().any(|w| w.contains('"')));
+        assert_eq!(
+            words("say \"Special Case\""),
+            vec!["say", "Special", "Case"]
+        );
+    }
 
     #[test]
     fn test_multiple_punctuation_merge() {
         let input = "Hello, world-test. \"Done!\"";
-        let result = split_text(input);
+        let result = words(input);
 
         // Expected: ["Hello,", "world-test.", "Done!"]
         assert_eq!(result[0], "Hello,");
@@ -160,7 +860,7 @@ mod tests {
     fn test_mixed_jieba_unicode() {
         // Test Case: English Contractions + Quoted Phrases + Chinese
         let input = "There's credibility to 'this time it's different' and 這是一個測試。";
-        let result = split_text(input);
+        let result = words(input);
 
         // Verify English contractions
         assert!(result.contains(&"There's".to_string()));
@@ -177,7 +877,7 @@ mod tests {
     #[test]
     fn test_single_quote() {
         let input = "There's some credibility to 'this time it's different'";
-        let result = split_text(input);
+        let result = words(input);
         let expected = vec![
             "There's",
             "some",
@@ -195,9 +895,205 @@ mod tests {
     #[test]
     fn test_ignore_single_punctuation() {
         let input = "That is - the result";
-        let result = split_text(input);
+        let result = words(input);
         let expected = vec!["That", "is", "the", "result"];
 
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn test_korean_segmentation() {
+        let input = "저는 학생입니다. 대한민국의수도는서울입니다.";
+        let result = words(input);
+
+        // Spaced words stay whole and keep their punctuation
+        assert_eq!(result[0], "저는");
+        assert_eq!(result[1], "학생입니다.");
+
+        // The unspaced run is split into several short pieces
+        let rest = &result[2..];
+        assert!(rest.len() > 1);
+        assert!(
+            rest.iter()
+                .all(|w| w.trim_end_matches('.').chars().count() <= 5)
+        );
+        assert_eq!(rest.concat(), "대한민국의수도는서울입니다.");
+    }
+
+    #[test]
+    fn test_split_at_orp() {
+        assert_eq!(split_at_orp("a"), ("", "a", ""));
+        assert_eq!(split_at_orp("word"), ("w", "o", "rd"));
+        assert_eq!(split_at_orp("recognition."), ("rec", "o", "gnition."));
+        assert_eq!(split_at_orp("這是測試"), ("這", "是", "測試"));
+    }
+
+    #[test]
+    fn test_decimal_number_preserved() {
+        let result = words("Pi is 3.14159");
+
+        assert_eq!(result, vec!["Pi", "is", "3.14159"]);
+    }
+
+    #[test]
+    fn test_thousands_separator_preserved() {
+        let result = words("He earned 1,000,000 dollars");
+        assert_eq!(result, vec!["He", "earned", "1,000,000", "dollars"]);
+
+        // Jieba path
+        let result = words("共1,000,000元");
+        assert!(result.contains(&"1,000,000".to_string()));
+    }
+
+    #[test]
+    fn test_url_and_email_kept_whole() {
+        let result = words("Read https://example.com/path?q=1 or mail me@example.org today.");
+
+        assert_eq!(
+            result,
+            vec![
+                "Read",
+                "https://example.com/path?q=1",
+                "or",
+                "mail",
+                "me@example.org",
+                "today."
+            ]
+        );
+
+        // Trailing punctuation stays attached like any other word
+        let result = words("See www.example.com.");
+        assert_eq!(result, vec!["See", "www.example.com."]);
+    }
+
+    #[test]
+    fn test_jieba_user_dictionary() {
+        let text = "我在蔚藍書店買書";
+        assert!(!words(text).contains(&"蔚藍書店".to_string()));
+
+        let path = std::env::temp_dir().join(format!("src-cli-{}-dict.txt", std::process::id()));
+        std::fs::write(&path, "蔚藍書店\n").unwrap();
+        let jieba = load_jieba(Some(&path)).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert!(split_words(text, Some(&jieba)).contains(&"蔚藍書店".to_string()));
+    }
+
+    #[test]
+    fn test_sentence_end_tokens() {
+        let tokens = split_text("Dr. Smith left. 他走了。真的嗎？", Some(&JIEBA), &[]);
+        let ends: Vec<&str> = tokens
+            .iter()
+            .filter(|token| token.ends_sentence)
+            .map(|token| token.text.as_str())
+            .collect();
+
+        assert_eq!(ends, vec!["left.", "了。", "嗎？"]);
+    }
+
+    #[test]
+    fn test_paragraph_boundary() {
+        let input = "First paragraph ends here.\r\n  \r\nSecond one\nwraps lines.";
+        let tokens = split_text(input, Some(&JIEBA), &[]);
+        let ends: Vec<&str> = tokens
+            .iter()
+            .filter(|token| token.ends_paragraph)
+            .map(|token| token.text.as_str())
+            .collect();
+
+        // A single newline doesn't split, and the final word isn't a boundary
+        assert_eq!(ends, vec!["here."]);
+        assert_eq!(tokens.len(), 8);
+    }
+
+    #[test]
+    fn test_without_jieba() {
+        assert!(!has_cjk("Hello, world"));
+        assert!(has_cjk("我們去吃飯"));
+
+        let tokens = split_text("Plain English text.", None, &[]);
+        assert_eq!(tokens.len(), 3);
+        assert!(tokens[2].ends_sentence);
+        // CJK still produces tokens on Unicode word bounds
+        assert!(!split_text("我們去吃飯", None, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_trailing_hyphen() {
+        assert_eq!(words("co- operation"), vec!["co-", "operation"]);
+        assert_eq!(words("a well-known fact"), vec!["a", "well-known", "fact"]);
+        // A word ending in a bare hyphen keeps it, at the end of the text too
+        assert_eq!(words("pre-"), vec!["pre-"]);
+        assert_eq!(words("dash-."), vec!["dash-."]);
+    }
+
+    #[test]
+    fn test_long_word_is_split() {
+        let blob = "QUJD".repeat(125);
+        let tokens = split_long_words(split_text(&format!("{}.", blob), None, &[]), 40);
+
+        assert!(tokens.len() > 1);
+        assert!(tokens.iter().all(|token| token.text.chars().count() <= 40));
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|token| token.text.as_str())
+                .collect::<String>(),
+            format!("{}.", blob)
+        );
+        // Only the last piece ends the sentence
+        assert_eq!(tokens.iter().filter(|token| token.ends_sentence).count(), 1);
+        assert!(tokens.last().unwrap().ends_sentence);
+    }
+
+    #[test]
+    fn test_rtl_words() {
+        // Arabic and Hebrew split on spaces like other scripts, keeping logical order
+        assert_eq!(words("مرحبا بالعالم"), vec!["مرحبا", "بالعالم"]);
+        assert_eq!(words("שלום עולם."), vec!["שלום", "עולם."]);
+        assert!(is_rtl("مرحبا") && is_rtl("עולם."));
+        assert!(!is_rtl("hello") && !is_rtl("你好"));
+    }
+
+    #[test]
+    fn test_emphasis_scoring() {
+        assert!(is_emphasized("photosynthesis,", 8, true));
+        assert!(!is_emphasized("leaf", 8, true));
+        // Long but common words only count with the stoplist off
+        assert!(!is_emphasized("Something.", 8, true));
+        assert!(is_emphasized("Something.", 8, false));
+    }
+
+    #[test]
+    fn test_strip_markdown() {
+        let markdown = "# Title #\n\n> Quote with **bold** and _italic_ text.\n\n- First [link](https://example.com)\n2. Second ![a chart](chart.png)\n\n---\n\n```rust\nlet x = 1;\n```\nUse `snake_case` and 2*3, ~~not~~ this [sic] [here](#).";
+        assert_eq!(
+            strip_markdown(markdown),
+            "Title\n\nQuote with bold and italic text.\n\nFirst link\nSecond a chart\n\n\n\n\nlet x = 1;\n\nUse snake_case and 2*3, not this [sic] here."
+        );
+
+        let words: Vec<String> = split_text(&strip_markdown(markdown), None, &[])
+            .into_iter()
+            .map(|token| token.text)
+            .collect();
+        assert!(
+            words
+                .iter()
+                .all(|word| !word.contains(['#', '*', '`', '[']))
+        );
+        assert_eq!(words[0], "Title");
+    }
+
+    #[test]
+    fn test_invisible_characters_are_dropped() {
+        let tokens = split_text(
+            "\u{FEFF}Hello \u{200B} wo\u{00AD}rld\u{200B}.\u{7}",
+            None,
+            &[],
+        );
+        let words: Vec<&str> = tokens.iter().map(|token| token.text.as_str()).collect();
+
+        assert_eq!(words, vec!["Hello", "world."]);
+        assert!(tokens[1].ends_sentence);
+    }
 }
@@ -0,0 +1,139 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Built-in command templates; {text} and {out} are substituted per argument
+const ENGINE_TEMPLATES: &[(&str, &str)] = &[
+    ("espeak", "espeak-ng -w {out} {text}"),
+    (
+        "say",
+        "say --file-format=WAVE --data-format=LEI16@22050 -o {out} {text}",
+    ),
+];
+
+// Synthesized narration track
+pub struct Narration {
+    pub path: PathBuf,
+    // atempo factor that stretches the narration to the video length
+    pub tempo: f64,
+    // Seconds of silence before the narration starts
+    pub delay: f64,
+}
+
+impl Narration {
+    // atempo only accepts 0.5-2.0 per instance on older FFmpeg, so chain it
+    pub fn audio_filters(&self) -> Vec<String> {
+        let mut filters = Vec::new();
+        let mut tempo = self.tempo;
+
+        while tempo > 2.0 {
+            filters.push("atempo=2.0".to_string());
+            tempo /= 2.0;
+        }
+        while tempo < 0.5 {
+            filters.push("atempo=0.5".to_string());
+            tempo /= 0.5;
+        }
+        filters.push(format!("atempo={}", tempo));
+
+        if self.delay > 0.0 {
+            filters.push(format!("adelay={}:all=1", (self.delay * 1000.0).round()));
+        }
+
+        filters
+    }
+}
+
+// The track is a temporary file, so it goes away with the narration on every exit path
+impl Drop for Narration {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+// Synthesize narration for the text. Failures only warn, so the video still renders.
+pub fn synthesize(
+    engine: &str,
+    template: Option<&str>,
+    text: &str,
+    total_duration: f64,
+) -> Option<Narration> {
+    let Some(template) = template.or_else(|| {
+        ENGINE_TEMPLATES
+            .iter()
+            .find(|(name, _)| *name == engine)
+            .map(|(_, template)| *template)
+    }) else {
+        info!(
+            "Warning: Unknown TTS engine '{}'. Provide --tts-command, continuing without narration",
+            engine
+        );
+        return None;
+    };
+
+    let out = std::env::temp_dir().join(format!("src-cli-{}-tts.wav", std::process::id()));
+    let out_str = out.to_string_lossy();
+
+    let mut parts = template
+        .split_whitespace()
+        .map(|part| part.replace("{text}", text).replace("{out}", &out_str));
+    let program = parts.next()?;
+
+    match Command::new(&program).args(parts).output() {
+        Ok(output) if output.status.success() => {}
+        Ok(output) => {
+            info!(
+                "Warning: TTS command failed, continuing without narration:\n{}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            return None;
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            info!(
+                "Warning: TTS command '{}' not found, continuing without narration",
+                program
+            );
+            return None;
+        }
+        Err(e) => {
+            info!(
+                "Warning: Failed to run TTS command: {}, continuing without narration",
+                e
+            );
+            return None;
+        }
+    }
+
+    let tempo = match probe_duration(&out) {
+        Some(duration) if total_duration > 0.0 => duration / total_duration,
+        _ => 1.0,
+    };
+
+    info!("Narration synthesized with {}", engine);
+    Some(Narration {
+        path: out,
+        tempo,
+        delay: 0.0,
+    })
+}
+
+fn probe_duration(path: &Path) -> Option<f64> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-show_entries",
+            "format=duration",
+            "-of",
+            "csv=p=0",
+        ])
+        .arg(path)
+        .output()
+        .ok()?;
+
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|duration| *duration > 0.0)
+}
@@ -0,0 +1,498 @@
+//! Convert text to speed-reading videos with FFmpeg.
+//!
+//! The `src-cli` binary is a thin wrapper around [`run`]. To render from another
+//! program, build [`VideoOptions`] and call [`generate`].
+
+use anyhow::{Context, Result};
+use clap::{CommandFactory, FromArgMatches, Parser};
+
+#[macro_use]
+mod log;
+
+mod config;
+mod error;
+mod ffmpeg;
+mod options;
+
+pub use error::Error;
+pub use ffmpeg::text::{Token, has_cjk, load_jieba, split_long_words, split_text, strip_markdown};
+pub use ffmpeg::{
+    Align, Container, HwEncode, Renderer, Theme, Watermark, WatermarkPosition, validate_color,
+};
+pub use options::{Output, VideoOptions};
+
+/// Convert text to video using FFmpeg
+#[derive(Parser, Debug, Clone)]
+#[command(author="s8508235", version, about, long_about = None)]
+struct Args {
+    /// Input text (if not provided, reads from stdin)
+    #[arg(short, long)]
+    text: Option<String>,
+
+    /// Input text file (used when --text is not provided)
+    #[arg(short, long)]
+    input_file: Option<std::path::PathBuf>,
+
+    /// EPUB book to read a chapter from (used when --text and --input-file are not provided)
+    #[arg(long, default_value = None, conflicts_with = "input_file")]
+    epub: Option<std::path::PathBuf>,
+
+    /// Chapter of the --epub book, 1-based in reading order (default: 1)
+    #[arg(long, default_value = "1", requires = "epub")]
+    chapter: usize,
+
+    /// Encoding of piped or file input, e.g. gbk, shift_jis, big5 (default: UTF-8)
+    #[arg(long, default_value = None)]
+    input_encoding: Option<String>,
+
+    /// First word to render, 0-based (default: 0)
+    #[arg(long, default_value = None)]
+    start_word: Option<usize>,
+
+    /// Word index to stop before, exclusive (default: end of text)
+    #[arg(long, default_value = None)]
+    end_word: Option<usize>,
+
+    /// Play the words from last to first, pausing at the same gaps
+    #[arg(long)]
+    reverse: bool,
+
+    /// Render only the first N seconds, as fast as possible, to check the look
+    #[arg(long, default_value = None)]
+    preview: Option<f64>,
+
+    /// Play the whole passage this many times, with a paragraph pause between passes (default: 1)
+    #[arg(long, default_value = "1")]
+    repeat: u32,
+
+    /// Refuse to render videos longer than this many seconds (default: unlimited)
+    #[arg(long, default_value = None)]
+    max_duration: Option<f64>,
+
+    /// Output video file path
+    #[arg(short, long, default_value = "output.mp4")]
+    output: String,
+
+    /// Output format (default: inferred from the --output extension)
+    #[arg(long, value_enum, default_value = None)]
+    format: Option<ffmpeg::Container>,
+
+    /// Words per minute (default: 300)
+    #[arg(short, long, default_value = "300")]
+    wpm: u32,
+
+    /// Fixed seconds per word, overriding --wpm, e.g. 0.25 (default: derived from --wpm)
+    #[arg(long, default_value = None)]
+    spw: Option<f64>,
+
+    /// Color preset for text, background and focus lines; explicit color flags still win
+    #[arg(long, value_enum, default_value = None)]
+    theme: Option<ffmpeg::Theme>,
+
+    /// Text color (default: #ffffee)
+    #[arg(long, default_value = "#ffffee")]
+    text_color: String,
+
+    /// Background color (default: black)
+    #[arg(long, default_value = "black")]
+    bg_color: String,
+
+    /// Fade the background from --bg-color at the top to this color at the bottom
+    #[arg(long, default_value = None)]
+    bg_gradient: Option<String>,
+
+    /// Darken the corners of the background with FFmpeg's vignette filter
+    #[arg(long)]
+    vignette: bool,
+
+    /// Gaussian blur sigma for the background, keeping the words sharp, e.g. 8 (default: off)
+    #[arg(long, default_value = None)]
+    blur_background: Option<f64>,
+
+    /// Still image drawn behind the words instead of --bg-color, scaled to fill the frame
+    #[arg(long, default_value = None)]
+    background_image: Option<std::path::PathBuf>,
+
+    /// Show focus lines around the word, also when the config turns them off (default: true)
+    #[arg(
+        long,
+        action = clap::ArgAction::SetTrue,
+        default_value_t = true,
+        overrides_with = "no_focus_lines"
+    )]
+    focus_lines: std::primitive::bool,
+
+    /// Hide the focus lines, also when the config turns them on
+    #[arg(long, overrides_with = "focus_lines")]
+    no_focus_lines: bool,
+
+    /// Focus line thickness in pixels (default: 10)
+    #[arg(long, default_value = "10")]
+    focus_thickness: u32,
+
+    /// Focus line distance from the top/bottom edge as a fraction of height (default: 0.2)
+    #[arg(long, default_value = "0.2")]
+    focus_offset: f64,
+
+    /// Color of the WPM label, and of the focus lines unless --focus-color is set (default: #1a1911)
+    #[arg(long, default_value = "#1a1911")]
+    secondary_color: String,
+
+    /// Focus line color (default: --secondary-color)
+    #[arg(long, default_value = None)]
+    focus_color: Option<String>,
+
+    /// Corner label: the WPM, custom text, or nothing (default: wpm)
+    #[arg(long, value_enum, default_value_t = ffmpeg::Watermark::Wpm)]
+    watermark: ffmpeg::Watermark,
+
+    /// Label text for --watermark custom
+    #[arg(long, default_value = None)]
+    watermark_text: Option<String>,
+
+    /// Corner for the watermark (default: bottom-right)
+    #[arg(long, value_enum, default_value_t = ffmpeg::WatermarkPosition::BottomRight)]
+    watermark_position: ffmpeg::WatermarkPosition,
+
+    /// Align each word on its optimal recognition point (pivot letter) at the focus mark
+    #[arg(long)]
+    orp: bool,
+
+    /// Color for the pivot letter in --orp mode (default: same as text)
+    #[arg(long, default_value = None)]
+    pivot_color: Option<String>,
+
+    /// Highlight long, uncommon words in --emphasis-color
+    #[arg(long)]
+    auto_emphasize: bool,
+
+    /// Color for words picked by --auto-emphasize (default: gold)
+    #[arg(long, default_value = "gold")]
+    emphasis_color: String,
+
+    /// Letters a word needs before --auto-emphasize highlights it (default: 8)
+    #[arg(long, default_value = "8")]
+    emphasis_min_length: usize,
+
+    /// Let --auto-emphasize highlight common long words like "something" (default: false)
+    #[arg(long, default_value_t = false)]
+    no_emphasis_stoplist: bool,
+
+    /// Also show the previous and next word, dimmed, on either side of the current one
+    #[arg(long)]
+    context_words: bool,
+
+    /// Color for --context-words, e.g. #FFFFEE40 (default: text color at 40% opacity)
+    #[arg(long, default_value = None)]
+    context_color: Option<String>,
+
+    /// Draw a thin bar along the bottom edge that fills as the words play
+    #[arg(long)]
+    show_progress_overlay: bool,
+
+    /// Count down this many seconds before the first word (default: 0)
+    #[arg(long, default_value = "0")]
+    countdown: u32,
+
+    /// Text held centered after the last word, e.g. "The End"
+    #[arg(long, default_value = None)]
+    outro_text: Option<String>,
+
+    /// Seconds to hold --outro-text (default: 2)
+    #[arg(long, default_value = "2")]
+    outro_duration: f64,
+
+    /// Words shown together per frame (default: 1)
+    #[arg(long, default_value = "1")]
+    chunk_size: usize,
+
+    /// Rest duration in seconds between sentences for blinking (default: 0.1)
+    #[arg(long, default_value = "0.1")]
+    rest_duration: f64,
+
+    /// Blank seconds at the end of each word's display time, keeping the pacing (default: 0)
+    #[arg(long, default_value = "0")]
+    word_gap: f64,
+
+    /// Pause after commas, as a multiple of --rest-duration (default: 0)
+    #[arg(long, default_value = "0")]
+    comma_pause: f64,
+
+    /// Pause after sentences, as a multiple of --rest-duration (default: 1)
+    #[arg(long, default_value = "1")]
+    sentence_pause: f64,
+
+    /// Blank pause between paragraphs (blank lines), as a multiple of --rest-duration (default: 2)
+    #[arg(long, default_value = "2")]
+    paragraph_pause: f64,
+
+    /// Insert a rest at least this often (seconds) even without sentence ends (default: 60)
+    #[arg(long, default_value = "60")]
+    periodic_rest_interval: f64,
+
+    /// Extra abbreviations (comma-separated) that don't end a sentence, e.g. "approx,dept"
+    #[arg(long, value_delimiter = ',')]
+    abbreviations: Vec<String>,
+
+    /// Jieba user dictionary, one "word [freq] [tag]" per line, to keep terms whole
+    #[arg(long, default_value = None)]
+    jieba_dict: Option<std::path::PathBuf>,
+
+    /// Hard-split words longer than this many characters, e.g. pasted base64 (default: 40)
+    #[arg(long, default_value = "40")]
+    max_word_length: usize,
+
+    /// Remove Markdown headings, emphasis, code fences and link targets before splitting (default: false)
+    #[arg(long, default_value_t = false)]
+    strip_markdown: bool,
+
+    /// Skip Jieba and split CJK text on Unicode word bounds (default: false)
+    #[arg(long, default_value_t = false)]
+    no_cjk: bool,
+
+    // local bgm location for webm; comma-separated or repeated for a playlist
+    #[arg(long, value_delimiter = ',')]
+    bgm_location: Vec<String>,
+
+    // Set when bgm_location came from the config file rather than the command line
+    #[arg(skip)]
+    bgm_from_config: bool,
+
+    /// Shuffle the BGM playlist
+    #[arg(long)]
+    bgm_shuffle: bool,
+
+    /// Seed for shuffling and other randomness, for reproducible output (default: random)
+    #[arg(long, default_value = None)]
+    seed: Option<u64>,
+
+    /// Extra plays of the BGM after the first: -1 loops forever, 0 plays once (default: -1)
+    #[arg(long, default_value = "-1", allow_negative_numbers = true)]
+    bgm_loop: i32,
+
+    /// BGM volume as a linear gain from 0.0 (silent) to 1.0 (original level)
+    #[arg(long, default_value = None)]
+    bgm_volume: Option<f64>,
+
+    /// BGM fade-in duration in seconds
+    #[arg(long, default_value = None)]
+    bgm_fade_in: Option<f64>,
+
+    /// BGM fade-out duration in seconds, ending with the video
+    #[arg(long, default_value = None)]
+    bgm_fade_out: Option<f64>,
+
+    /// Narrate the text with a TTS engine (espeak, say, or any name with --tts-command)
+    #[arg(long, default_value = None)]
+    tts: Option<String>,
+
+    /// TTS command template; {text} and {out} (a WAV path) are substituted
+    #[arg(long, default_value = None)]
+    tts_command: Option<String>,
+
+    // local font location for output text
+    #[arg(long, default_value = None)]
+    font_location: Option<String>,
+
+    /// Overwrite the output file if it exists; without it an existing file is an error
+    #[arg(short = 'y', long, alias = "overwrite-output-file")]
+    overwrite: bool,
+
+    /// Output resolution as WIDTHxHEIGHT (default: 1920x1080)
+    #[arg(long, default_value = None)]
+    resolution: Option<String>,
+
+    /// Output frame rate, 1-240 (default: 30)
+    #[arg(long, default_value = "30")]
+    fps: u32,
+
+    /// Font size for regular words (default: 100)
+    #[arg(long, default_value = "100")]
+    fontsize: u32,
+
+    /// Font size for words longer than --long-word-threshold (default: 80)
+    #[arg(long, default_value = "80")]
+    fontsize_small: u32,
+
+    /// Character count above which a word uses --fontsize-small (default: 50)
+    #[arg(long, default_value = "50")]
+    long_word_threshold: usize,
+
+    /// Extra letter spacing, in thin spaces inserted between letters (default: 0)
+    #[arg(long, default_value = "0")]
+    letter_spacing: u32,
+
+    /// drawtext line spacing in pixels for words that span lines (default: 0)
+    #[arg(long, default_value = "0", allow_negative_numbers = true)]
+    line_spacing: i32,
+
+    /// Shrink each word's font size until its estimated width fits the frame
+    #[arg(long)]
+    font_size_auto: bool,
+
+    /// Horizontal padding in pixels on each side for --font-size-auto (default: 5% of the width)
+    #[arg(long, default_value = None)]
+    margin: Option<u32>,
+
+    /// Stack the characters of Chinese and Japanese words top to bottom (drawtext renderer only)
+    #[arg(long)]
+    vertical: bool,
+
+    /// Horizontal word position: left (the reading offset), center or right (default: left)
+    #[arg(long, value_enum, default_value_t = ffmpeg::Align::Left)]
+    align: ffmpeg::Align,
+
+    /// drawtext x expression for the word without --orp, overriding --align, e.g. "w*0.3"
+    /// (default: from --align)
+    #[arg(long, default_value = None)]
+    word_x: Option<String>,
+
+    /// How words are drawn: drawtext filters, an ASS subtitle file, or auto by word count
+    #[arg(long, value_enum, default_value_t = ffmpeg::Renderer::Auto)]
+    renderer: ffmpeg::Renderer,
+
+    /// Word count above which --renderer auto switches to ASS (default: 500)
+    #[arg(long, default_value = "500")]
+    ass_word_threshold: usize,
+
+    /// Video encoder passed to FFmpeg's -c:v (default: libx264, libvpx-vp9 for .webm)
+    #[arg(long, default_value = "libx264")]
+    vcodec: String,
+
+    /// Encoder preset (default: ultrafast for libx264, encoder default otherwise)
+    #[arg(long, default_value = None)]
+    preset: Option<String>,
+
+    /// Constant rate factor (default: 23 for libx264, encoder default otherwise)
+    #[arg(long, default_value = None)]
+    crf: Option<u32>,
+
+    /// Pixel format passed to FFmpeg's -pix_fmt, e.g. yuv444p or yuv420p10le (default: yuv420p)
+    #[arg(long, default_value = "yuv420p")]
+    pix_fmt: String,
+
+    /// Audio encoder for BGM and narration, e.g. libopus (default: aac, libopus for .webm)
+    #[arg(long, default_value = "aac", alias = "audio-codec")]
+    acodec: String,
+
+    /// Audio bitrate for BGM and narration, e.g. 128k or 96000 (default: 192k)
+    #[arg(long, default_value = "192k", alias = "audio-bitrate")]
+    abitrate: String,
+
+    /// Render in N parallel FFmpeg processes, each encoding a slice of the words, then join them (default: 1)
+    #[arg(long, default_value = "1")]
+    jobs: usize,
+
+    /// Hardware encoder: auto picks the first available of nvenc/qsv/videotoolbox/vaapi
+    #[arg(long, value_enum, default_value_t = ffmpeg::HwEncode::None)]
+    hwenc: ffmpeg::HwEncode,
+
+    /// Hardware decoding method passed to FFmpeg's -hwaccel, e.g. none or cuda (default: auto)
+    #[arg(long, default_value = "auto")]
+    hwaccel: String,
+
+    /// Print a JSON summary after rendering instead of progress output
+    #[arg(long)]
+    json: bool,
+
+    /// Print render speed as "benchmark realtime_factor=... words_per_second=... render_seconds=..."
+    #[arg(long)]
+    benchmark: bool,
+
+    /// Print the default font candidates for this OS, marking which exist, and exit
+    #[arg(long)]
+    list_fonts: bool,
+
+    /// Print the FFmpeg command instead of running it
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Print each word with its start/end time and exit without rendering
+    #[arg(long)]
+    print_words: bool,
+
+    /// Write word timings to an SRT subtitle file
+    #[arg(long)]
+    srt: Option<std::path::PathBuf>,
+
+    /// Only write the SRT file and skip rendering the video
+    #[arg(long, requires = "srt")]
+    srt_only: bool,
+
+    /// Only print errors
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Also print the filter chain and the FFmpeg command
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Config file to use instead of the default location
+    #[arg(long, global = true)]
+    config: Option<std::path::PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(clap::Subcommand, Debug, Clone)]
+enum Commands {
+    /// Manage the config file
+    Config {
+        #[command(subcommand)]
+        action: config::ConfigAction,
+    },
+    /// Validate the config, options, input text and font without rendering
+    Check,
+}
+
+/// Run the command line interface
+pub fn run() -> Result<()> {
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    // overwrite config if args not present
+    config::merge_config_with_args(&mut args, &matches)
+        .with_context(|| "Failed to merge configuration with arguments")?;
+
+    // JSON output must be the only thing on stdout
+    log::set_level(args.quiet || args.json, args.verbose);
+
+    match &args.command {
+        Some(Commands::Config { action }) => return config::run_config_action(action, &args),
+        Some(Commands::Check) => return ffmpeg::check(args),
+        None => {}
+    }
+
+    if args.list_fonts {
+        ffmpeg::list_fonts();
+        return Ok(());
+    }
+
+    // Check if ffmpeg is available (not needed when only exporting subtitles or words)
+    if !args.srt_only && !args.print_words {
+        ffmpeg::check_ffmpeg()?;
+    }
+
+    let (json, benchmark) = (args.json, args.benchmark);
+    if let Some(output) = ffmpeg::generate_video(args)? {
+        if json {
+            println!("{}", serde_json::to_string(&output)?);
+        } else if benchmark {
+            println!(
+                "benchmark realtime_factor={:.3} words_per_second={:.1} render_seconds={:.3}",
+                output.realtime_factor, output.words_per_second, output.render_seconds
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Render a video without touching the command line, stdin, or config files.
+pub fn generate(options: VideoOptions) -> std::result::Result<Output, Error> {
+    let args = options.into_args();
+    log::set_level(args.quiet, args.verbose);
+
+    ffmpeg::check_ffmpeg()?;
+    Ok(ffmpeg::generate_video(args)?.context("No video was rendered")?)
+}
@@ -0,0 +1,185 @@
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+use serde::Serialize;
+
+use crate::ffmpeg::{Align, Renderer, Theme, Watermark};
+
+/// Result of a rendered video. Also printed by `--json`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Output {
+    pub word_count: usize,
+    pub wpm: u32,
+    pub seconds_per_word: f64,
+    pub total_duration: f64,
+    pub output_path: PathBuf,
+    /// Font file the words were drawn with, after OS default detection
+    pub font_location: String,
+    pub render_seconds: f64,
+    /// Seconds of video encoded per second of rendering (x realtime)
+    pub realtime_factor: f64,
+    pub words_per_second: f64,
+}
+
+/// Builder for [`crate::generate`]. Unset options use the command line defaults.
+#[derive(Debug)]
+pub struct VideoOptions {
+    args: crate::Args,
+}
+
+impl VideoOptions {
+    pub fn new(text: impl Into<String>) -> Self {
+        // An empty command line keeps the defaults defined once, on Args
+        let mut args = crate::Args::parse_from(["src-cli"]);
+        args.text = Some(text.into());
+        args.quiet = true;
+
+        VideoOptions { args }
+    }
+
+    pub fn output(mut self, path: impl AsRef<Path>) -> Self {
+        self.args.output = path.as_ref().to_string_lossy().into_owned();
+        self
+    }
+
+    pub fn wpm(mut self, wpm: u32) -> Self {
+        self.args.wpm = wpm;
+        self
+    }
+
+    /// Set all three colors from a preset; later color calls still override it
+    pub fn theme(mut self, theme: Theme) -> Self {
+        let (text, bg, secondary) = theme.colors();
+        self.args.theme = Some(theme);
+        self.args.text_color = text.to_string();
+        self.args.bg_color = bg.to_string();
+        self.args.secondary_color = secondary.to_string();
+        self
+    }
+
+    pub fn text_color(mut self, color: impl Into<String>) -> Self {
+        self.args.text_color = color.into();
+        self
+    }
+
+    pub fn bg_color(mut self, color: impl Into<String>) -> Self {
+        self.args.bg_color = color.into();
+        self
+    }
+
+    pub fn background_image(mut self, path: impl Into<PathBuf>) -> Self {
+        self.args.background_image = Some(path.into());
+        self
+    }
+
+    pub fn font_location(mut self, path: impl AsRef<Path>) -> Self {
+        self.args.font_location = Some(path.as_ref().to_string_lossy().into_owned());
+        self
+    }
+
+    pub fn resolution(mut self, width: u32, height: u32) -> Self {
+        self.args.resolution = Some(format!("{}x{}", width, height));
+        self
+    }
+
+    pub fn fps(mut self, fps: u32) -> Self {
+        self.args.fps = fps;
+        self
+    }
+
+    pub fn fontsize(mut self, fontsize: u32) -> Self {
+        self.args.fontsize = fontsize;
+        self
+    }
+
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.args.chunk_size = chunk_size;
+        self
+    }
+
+    pub fn orp(mut self, orp: bool) -> Self {
+        self.args.orp = orp;
+        self
+    }
+
+    pub fn align(mut self, align: Align) -> Self {
+        self.args.align = align;
+        self
+    }
+
+    pub fn bgm<P: AsRef<Path>>(mut self, paths: impl IntoIterator<Item = P>) -> Self {
+        self.args.bgm_location = paths
+            .into_iter()
+            .map(|path| path.as_ref().to_string_lossy().into_owned())
+            .collect();
+        self
+    }
+
+    pub fn srt(mut self, path: impl Into<PathBuf>) -> Self {
+        self.args.srt = Some(path.into());
+        self
+    }
+
+    pub fn vcodec(mut self, vcodec: impl Into<String>) -> Self {
+        self.args.vcodec = vcodec.into();
+        self
+    }
+
+    pub fn renderer(mut self, renderer: Renderer) -> Self {
+        self.args.renderer = renderer;
+        self
+    }
+
+    pub fn watermark(mut self, watermark: Watermark) -> Self {
+        self.args.watermark = watermark;
+        self
+    }
+
+    pub fn overwrite(mut self, overwrite: bool) -> Self {
+        self.args.overwrite = overwrite;
+        self
+    }
+
+    /// Print progress like the command line does (quiet by default)
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.args.quiet = !verbose;
+        self
+    }
+
+    pub(crate) fn into_args(self) -> crate::Args {
+        self.args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_match_cli() {
+        let args = VideoOptions::new("Hello").into_args();
+
+        assert_eq!(args.text.as_deref(), Some("Hello"));
+        assert_eq!(args.wpm, 300);
+        assert_eq!(args.fps, 30);
+        assert_eq!(args.output, "output.mp4");
+        assert!(args.quiet);
+    }
+
+    #[test]
+    fn test_builder_sets_options() {
+        let args = VideoOptions::new("Hello")
+            .output("clip.webm")
+            .wpm(450)
+            .resolution(1080, 1920)
+            .bgm(["a.mp3", "b.mp3"])
+            .verbose(true)
+            .into_args();
+
+        assert_eq!(args.output, "clip.webm");
+        assert_eq!(args.wpm, 450);
+        assert_eq!(args.resolution.as_deref(), Some("1080x1920"));
+        assert_eq!(args.bgm_location, vec!["a.mp3", "b.mp3"]);
+        assert!(!args.quiet);
+    }
+}